@@ -0,0 +1,272 @@
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::mutex::RawSpin;
+use crate::waker_set::{Waiter, WakerSet};
+
+///
+/// 异步互斥锁
+/// `lock`返回一个future，拿不到锁时登记任务的Waker并返回`Pending`，
+/// 不会像[`Mutex`](crate::Mutex)那样自旋阻塞整个执行器。
+/// 不依赖具体的执行器，也不需要堆分配
+/// # Exapmle
+///
+/// ```
+/// use xx_mutex_lock::AsyncMutex;
+///
+/// async fn add(locked: &AsyncMutex<i32>) {
+///     let mut lock_guard = locked.lock().await;
+///     *lock_guard += 1;
+/// }
+/// ```
+/// 当guard被drop时，自动解锁并唤醒等待的任务
+pub struct AsyncMutex<T: ?Sized> {
+    pub(crate) raw: RawSpin,
+    waiters: WakerSet,
+    data: UnsafeCell<T>,
+}
+
+/// 异步互斥锁守卫
+pub struct AsyncMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a AsyncMutex<T>,
+}
+
+/// [`AsyncMutex::lock`]返回的future
+/// 在拿到锁之前drop是安全的，会取消登记的Waker，不会影响其他等待的任务。
+/// Waker登记在future内部的节点上，所以future不实现`Unpin`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AsyncMutexLockFuture<'a, T: ?Sized + 'a> {
+    mutex: &'a AsyncMutex<T>,
+    waiter: Waiter,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for AsyncMutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for AsyncMutex<T> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for AsyncMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Send> Send for AsyncMutexGuard<'_, T> {}
+
+impl<T> AsyncMutex<T> {
+    loom_const_fn! {
+        pub const fn new(data: T) -> Self {
+            AsyncMutex {
+                raw: RawSpin::new(),
+                waiters: WakerSet::new(),
+                data: UnsafeCell::new(data),
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> AsyncMutex<T> {
+    /// 异步地上锁
+    pub fn lock(&self) -> AsyncMutexLockFuture<'_, T> {
+        AsyncMutexLockFuture {
+            mutex: self,
+            waiter: Waiter::new(),
+        }
+    }
+
+    /// 非阻塞地上锁，锁已被持有时立即返回None
+    #[inline]
+    pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T>> {
+        if self.raw.try_lock() {
+            Some(AsyncMutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Future for AsyncMutexLockFuture<'a, T> {
+    type Output = AsyncMutexGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.into_ref().get_ref();
+        if let Some(guard) = this.mutex.try_lock() {
+            this.mutex.waiters.remove(&this.waiter);
+            return Poll::Ready(guard);
+        }
+        //future已经被固定，节点在drop之前不会移动
+        let waiter = unsafe { Pin::new_unchecked(&this.waiter) };
+        this.mutex.waiters.register(waiter, cx.waker());
+        //登记之前锁可能刚好被释放，再试一次避免错过唤醒
+        match this.mutex.try_lock() {
+            Some(guard) => {
+                this.mutex.waiters.remove(&this.waiter);
+                Poll::Ready(guard)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncMutexLockFuture<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.waiters.remove(&self.waiter);
+    }
+}
+
+impl<'a, T: ?Sized> Deref for AsyncMutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for AsyncMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.mutex.raw.unlock() };
+        //唤醒所有等待的任务，即使其中有的future已经被drop，其他任务也不会错过唤醒
+        self.mutex.waiters.wake_all();
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::async_mutex::AsyncMutex;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::{Context, Poll, Waker};
+    use std::boxed::Box;
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::vec::Vec;
+
+    /// 记录被唤醒次数的Waker，唤醒时unpark对应的线程
+    pub(crate) struct TestWaker {
+        pub wakes: AtomicUsize,
+        thread: std::thread::Thread,
+    }
+
+    impl Wake for TestWaker {
+        fn wake(self: Arc<Self>) {
+            self.wakes.fetch_add(1, Ordering::SeqCst);
+            self.thread.unpark();
+        }
+    }
+
+    pub(crate) fn test_waker() -> (Arc<TestWaker>, Waker) {
+        let inner = Arc::new(TestWaker {
+            wakes: AtomicUsize::new(0),
+            thread: std::thread::current(),
+        });
+        (inner.clone(), Waker::from(inner))
+    }
+
+    /// 最简单的执行器，Pending时park当前线程
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let (_, waker) = test_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    #[test]
+    fn test() {
+        let lock = Arc::new(AsyncMutex::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    block_on(async {
+                        for _ in 0..100 {
+                            *lock.lock().await += 1;
+                        }
+                    })
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*block_on(lock.lock()), 400);
+        assert!(!lock.raw.is_locked());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let lock = AsyncMutex::new(0);
+        let guard = lock.try_lock().expect("err");
+
+        let (waker1, w1) = test_waker();
+        let (waker2, w2) = test_waker();
+        let mut fut1 = Box::pin(lock.lock());
+        let mut fut2 = pin!(lock.lock());
+        assert!(fut1
+            .as_mut()
+            .poll(&mut Context::from_waker(&w1))
+            .is_pending());
+        assert!(fut2
+            .as_mut()
+            .poll(&mut Context::from_waker(&w2))
+            .is_pending());
+
+        //第一个future被取消后，解锁仍然会唤醒第二个
+        drop(fut1);
+        drop(guard);
+        assert_eq!(0, waker1.wakes.load(Ordering::SeqCst));
+        assert_eq!(1, waker2.wakes.load(Ordering::SeqCst));
+
+        let Poll::Ready(mut guard) = fut2.as_mut().poll(&mut Context::from_waker(&w2)) else {
+            panic!("lock should be free");
+        };
+        *guard += 1;
+        drop(guard);
+        assert_eq!(*lock.try_lock().expect("err"), 1);
+    }
+
+    #[test]
+    fn test_many_waiters() {
+        //等待的任务比较多时，锁被持有期间不能互相唤醒
+        let lock = AsyncMutex::new(0);
+        let guard = lock.try_lock().expect("err");
+        let mut futures: Vec<_> = (0..16)
+            .map(|_| (Box::pin(lock.lock()), test_waker()))
+            .collect();
+        for _ in 0..3 {
+            for (future, (_, waker)) in &mut futures {
+                assert!(future
+                    .as_mut()
+                    .poll(&mut Context::from_waker(waker))
+                    .is_pending());
+            }
+        }
+        assert!(futures
+            .iter()
+            .all(|(_, (waker, _))| waker.wakes.load(Ordering::SeqCst) == 0));
+
+        //解锁时每个任务都被唤醒一次
+        drop(guard);
+        assert!(futures
+            .iter()
+            .all(|(_, (waker, _))| waker.wakes.load(Ordering::SeqCst) == 1));
+        let (future, (_, waker)) = &mut futures[15];
+        assert!(future
+            .as_mut()
+            .poll(&mut Context::from_waker(waker))
+            .is_ready());
+    }
+}
@@ -0,0 +1,348 @@
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use crate::rw_lock::RawRwSpin;
+use crate::waker_set::{Waiter, WakerSet};
+
+///
+/// 异步读写锁
+/// 和[`RWLock`](crate::RWLock)使用相同的状态编码，`read`和`write`返回future，
+/// 拿不到锁时登记任务的Waker而不是自旋。
+/// 有写者在等待时，新的读者不能再拿到读锁，避免源源不断的读者饿死写者。
+/// 不依赖具体的执行器，也不需要堆分配
+/// # Exapmle
+///
+/// ```
+/// use xx_mutex_lock::AsyncRWLock;
+///
+/// async fn add(locked: &AsyncRWLock<i32>) -> i32 {
+///     *locked.write().await += 1;
+///     *locked.read().await
+/// }
+/// ```
+pub struct AsyncRWLock<T: ?Sized> {
+    pub(crate) raw: RawRwSpin,
+    /// 正在等待写锁的写者数量，不为0时新的读者需要等待
+    writers_waiting: AtomicUsize,
+    readers: WakerSet,
+    writers: WakerSet,
+    data: UnsafeCell<T>,
+}
+
+/// 异步读锁守卫
+pub struct AsyncRWLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a AsyncRWLock<T>,
+}
+
+/// 异步写锁守卫
+pub struct AsyncRWLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a AsyncRWLock<T>,
+}
+
+/// [`AsyncRWLock::read`]返回的future，在拿到锁之前drop是安全的，不实现`Unpin`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AsyncRWLockReadFuture<'a, T: ?Sized + 'a> {
+    lock: &'a AsyncRWLock<T>,
+    waiter: Waiter,
+}
+
+/// [`AsyncRWLock::write`]返回的future，在拿到锁之前drop是安全的，不实现`Unpin`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AsyncRWLockWriteFuture<'a, T: ?Sized + 'a> {
+    lock: &'a AsyncRWLock<T>,
+    waiter: Waiter,
+    /// 是否已经计入`writers_waiting`
+    waiting: bool,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncRWLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AsyncRWLock<T> {}
+
+unsafe impl<T: ?Sized + Sync> Send for AsyncRWLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for AsyncRWLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Send + Sync> Send for AsyncRWLockWriteGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for AsyncRWLockWriteGuard<'_, T> {}
+
+impl<T> AsyncRWLock<T> {
+    loom_const_fn! {
+        pub const fn new(data: T) -> Self {
+            AsyncRWLock {
+                raw: RawRwSpin::new(),
+                writers_waiting: AtomicUsize::new(0),
+                readers: WakerSet::new(),
+                writers: WakerSet::new(),
+                data: UnsafeCell::new(data),
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> AsyncRWLock<T> {
+    /// 异步地获取读锁
+    pub fn read(&self) -> AsyncRWLockReadFuture<'_, T> {
+        AsyncRWLockReadFuture {
+            lock: self,
+            waiter: Waiter::new(),
+        }
+    }
+
+    /// 异步地获取写锁
+    pub fn write(&self) -> AsyncRWLockWriteFuture<'_, T> {
+        AsyncRWLockWriteFuture {
+            lock: self,
+            waiter: Waiter::new(),
+            waiting: false,
+        }
+    }
+
+    /// 非阻塞地获取读锁，有写者持有锁或者在等待时返回None
+    #[inline]
+    pub fn try_read(&self) -> Option<AsyncRWLockReadGuard<'_, T>> {
+        if self.writers_waiting.load(Ordering::SeqCst) == 0 && self.raw.try_read() {
+            Some(AsyncRWLockReadGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// 非阻塞地获取写锁
+    #[inline]
+    pub fn try_write(&self) -> Option<AsyncRWLockWriteGuard<'_, T>> {
+        if self.raw.try_write() {
+            Some(AsyncRWLockWriteGuard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Future for AsyncRWLockReadFuture<'a, T> {
+    type Output = AsyncRWLockReadGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.into_ref().get_ref();
+        if let Some(guard) = this.lock.try_read() {
+            this.lock.readers.remove(&this.waiter);
+            return Poll::Ready(guard);
+        }
+        //future已经被固定，节点在drop之前不会移动
+        let waiter = unsafe { Pin::new_unchecked(&this.waiter) };
+        this.lock.readers.register(waiter, cx.waker());
+        //登记之前锁可能刚好被释放，再试一次避免错过唤醒
+        match this.lock.try_read() {
+            Some(guard) => {
+                this.lock.readers.remove(&this.waiter);
+                Poll::Ready(guard)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRWLockReadFuture<'a, T> {
+    fn drop(&mut self) {
+        self.lock.readers.remove(&self.waiter);
+    }
+}
+
+impl<'a, T: ?Sized> AsyncRWLockWriteFuture<'a, T> {
+    /// 拿到写锁后取消登记，并且不再计入等待的写者
+    fn finish(&mut self) -> AsyncRWLockWriteGuard<'a, T> {
+        self.lock.writers.remove(&self.waiter);
+        if self.waiting {
+            self.waiting = false;
+            self.lock.writers_waiting.fetch_sub(1, Ordering::SeqCst);
+        }
+        AsyncRWLockWriteGuard { lock: self.lock }
+    }
+}
+
+impl<'a, T: ?Sized> Future for AsyncRWLockWriteFuture<'a, T> {
+    type Output = AsyncRWLockWriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        //不会移动`waiter`
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.lock.raw.try_write() {
+            return Poll::Ready(this.finish());
+        }
+        if !this.waiting {
+            this.waiting = true;
+            this.lock.writers_waiting.fetch_add(1, Ordering::SeqCst);
+        }
+        //future已经被固定，节点在drop之前不会移动
+        let waiter = unsafe { Pin::new_unchecked(&this.waiter) };
+        this.lock.writers.register(waiter, cx.waker());
+        if this.lock.raw.try_write() {
+            Poll::Ready(this.finish())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRWLockWriteFuture<'a, T> {
+    fn drop(&mut self) {
+        self.lock.writers.remove(&self.waiter);
+        //最后一个等待的写者被取消时，被它挡住的读者需要重新尝试
+        if self.waiting && self.lock.writers_waiting.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.lock.readers.wake_all();
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for AsyncRWLockReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for AsyncRWLockWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for AsyncRWLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRWLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock_read() };
+        //最后一个读者离开时唤醒写者
+        if !self.lock.raw.is_locked() {
+            self.lock.writers.wake_all();
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRWLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock_write() };
+        //写者优先，没有写者在等待时才唤醒读者
+        if self.lock.writers_waiting.load(Ordering::SeqCst) == 0 {
+            self.lock.readers.wake_all();
+        } else {
+            self.lock.writers.wake_all();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::async_mutex::test::{block_on, test_waker};
+    use crate::async_rw_lock::AsyncRWLock;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::sync::atomic::Ordering;
+    use core::task::{Context, Poll};
+    use std::boxed::Box;
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn test() {
+        let lock = Arc::new(AsyncRWLock::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    block_on(async {
+                        for _ in 0..100 {
+                            if i % 2 == 0 {
+                                *lock.write().await += 1;
+                            } else {
+                                assert!(*lock.read().await >= 0);
+                            }
+                        }
+                    })
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*block_on(lock.read()), 200);
+        assert!(!lock.raw.is_locked());
+    }
+
+    #[test]
+    fn test_writer_fairness() {
+        let lock = AsyncRWLock::new(0);
+        let read = lock.try_read().expect("err");
+
+        let (writer, ww) = test_waker();
+        let (reader, rw) = test_waker();
+        let mut write = pin!(lock.write());
+        let mut read2 = pin!(lock.read());
+        assert!(write
+            .as_mut()
+            .poll(&mut Context::from_waker(&ww))
+            .is_pending());
+        //有写者在等待，新的读者拿不到读锁
+        assert!(lock.try_read().is_none());
+        assert!(read2
+            .as_mut()
+            .poll(&mut Context::from_waker(&rw))
+            .is_pending());
+
+        drop(read);
+        assert_eq!(1, writer.wakes.load(Ordering::SeqCst));
+        assert_eq!(0, reader.wakes.load(Ordering::SeqCst));
+        let Poll::Ready(mut guard) = write.as_mut().poll(&mut Context::from_waker(&ww)) else {
+            panic!("writer should get the lock");
+        };
+        *guard += 1;
+        drop(guard);
+
+        assert_eq!(1, reader.wakes.load(Ordering::SeqCst));
+        let Poll::Ready(guard) = read2.as_mut().poll(&mut Context::from_waker(&rw)) else {
+            panic!("reader should get the lock");
+        };
+        assert_eq!(*guard, 1);
+    }
+
+    #[test]
+    fn test_cancel_writer() {
+        let lock = AsyncRWLock::new(0);
+        let read = lock.try_read().expect("err");
+
+        let (_, ww) = test_waker();
+        let (reader, rw) = test_waker();
+        let mut write = Box::pin(lock.write());
+        let mut read2 = pin!(lock.read());
+        assert!(write
+            .as_mut()
+            .poll(&mut Context::from_waker(&ww))
+            .is_pending());
+        assert!(read2
+            .as_mut()
+            .poll(&mut Context::from_waker(&rw))
+            .is_pending());
+
+        //等待的写者被取消后，读者不再被挡住
+        drop(write);
+        assert_eq!(1, reader.wakes.load(Ordering::SeqCst));
+        assert!(read2
+            .as_mut()
+            .poll(&mut Context::from_waker(&rw))
+            .is_ready());
+        drop(read);
+    }
+}
@@ -0,0 +1,345 @@
+use alloc::boxed::Box;
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use crate::relax::{Relax, Spin};
+
+///
+/// CLH队列锁
+/// 每次上锁时新建一个节点挂到队尾，然后在前一个节点上自旋，
+/// 前一个节点解锁时只需修改自己节点的状态，不需要像MCS那样找到后继节点。
+/// 节点分配在堆上，所以只在`alloc` feature下可用
+/// # Exapmle
+///
+/// ```
+/// use xx_mutex_lock::ClhMutex;
+///
+/// let locked = ClhMutex::new(1);
+/// let mut lock_guard = locked.lock();
+/// *lock_guard += 1;
+/// assert_eq!(*lock_guard, 2)
+/// ```
+/// 当guard被drop时，自动解锁
+pub struct ClhMutex<T: ?Sized, R = Spin> {
+    pub(crate) raw: RawClh<R>,
+    data: UnsafeCell<T>,
+}
+
+/// 不保护任何数据的CLH队列锁，[`ClhMutex`]的底层实现
+/// 需要手动调用`lock`和`unlock`，持有者的节点保存在锁里，
+/// 在`lock_api` feature下实现了`lock_api::RawMutex`，参见[`crate::lock_api`]
+pub struct RawClh<R = Spin> {
+    /// 队尾的节点，为空表示没有人持有锁
+    pub(crate) tail: AtomicPtr<ClhNode>,
+    /// 通过`lock`/`try_lock`上锁时持有者的节点，只由持有者读写
+    holder: AtomicPtr<ClhNode>,
+    _relax: PhantomData<R>,
+}
+
+/// CLH队列中的节点
+/// 节点由上锁的线程创建，解锁后交给后继释放
+pub(crate) struct ClhNode {
+    locked: AtomicBool,
+}
+
+/// CLH互斥锁守卫
+/// 当guard被drop时，自动解锁
+pub struct ClhMutexGuard<'a, T: ?Sized + 'a> {
+    tail: &'a AtomicPtr<ClhNode>,
+    node: *mut ClhNode,
+    data: *mut T,
+}
+
+unsafe impl<T: ?Sized + Send, R> Sync for ClhMutex<T, R> {}
+unsafe impl<T: ?Sized + Send, R> Send for ClhMutex<T, R> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for ClhMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Send> Send for ClhMutexGuard<'_, T> {}
+
+impl ClhNode {
+    fn alloc() -> *mut ClhNode {
+        Box::into_raw(Box::new(ClhNode {
+            locked: AtomicBool::new(true),
+        }))
+    }
+
+    /// 解锁
+    ///
+    /// # Safety
+    /// `node`必须是持有锁时挂到`tail`上的节点，并且只能解锁一次
+    unsafe fn release(tail: &AtomicPtr<ClhNode>, node: *mut ClhNode) {
+        //没有后继时直接清空队列并释放自己的节点
+        if tail
+            .compare_exchange(node, ptr::null_mut(), Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+        {
+            drop(Box::from_raw(node));
+            return;
+        }
+        //有后继正在自己的节点上自旋，解锁后节点由后继释放
+        (*node).locked.store(false, Ordering::Release);
+    }
+}
+
+impl RawClh {
+    pub const fn new() -> Self {
+        Self::with_relax()
+    }
+}
+
+impl Default for RawClh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> RawClh<R> {
+    /// 使用指定的等待策略创建CLH锁
+    pub const fn with_relax() -> Self {
+        RawClh {
+            tail: AtomicPtr::new(ptr::null_mut()),
+            holder: AtomicPtr::new(ptr::null_mut()),
+            _relax: PhantomData,
+        }
+    }
+
+    /// 锁是否被持有(或有线程在排队)，只用于调试，返回值可能立即过时
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        !self.tail.load(Ordering::Relaxed).is_null()
+    }
+
+    /// 非阻塞地上锁，成功时返回挂到队列上的节点
+    #[inline]
+    fn try_acquire(&self) -> Option<*mut ClhNode> {
+        //先检查一次，避免在锁被持有时白白分配节点
+        if self.is_locked() {
+            return None;
+        }
+        let node = ClhNode::alloc();
+        if self
+            .tail
+            .compare_exchange(ptr::null_mut(), node, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            Some(node)
+        } else {
+            drop(unsafe { Box::from_raw(node) });
+            None
+        }
+    }
+
+    /// 非阻塞地上锁，只有在队列为空时才会成功，成功时返回true
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        match self.try_acquire() {
+            Some(node) => {
+                self.holder.store(node, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 解锁
+    ///
+    /// # Safety
+    /// 只能由通过`lock`或`try_lock`持有锁的一方调用
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        ClhNode::release(&self.tail, self.holder.load(Ordering::Relaxed))
+    }
+}
+
+impl<R: Relax> RawClh<R> {
+    /// 上锁，返回挂到队列上的节点
+    #[inline]
+    fn acquire(&self) -> *mut ClhNode {
+        let node = ClhNode::alloc();
+        let pred = self.tail.swap(node, Ordering::AcqRel);
+        if !pred.is_null() {
+            //在前一个节点上自旋，前一个节点解锁后就归我们所有
+            let mut relax = R::default();
+            while unsafe { (*pred).locked.load(Ordering::Acquire) } {
+                relax.relax();
+            }
+            drop(unsafe { Box::from_raw(pred) });
+        }
+        node
+    }
+
+    /// 上锁，前一个节点没有解锁时按照`R`的策略等待
+    #[inline]
+    pub fn lock(&self) {
+        let node = self.acquire();
+        self.holder.store(node, Ordering::Relaxed);
+    }
+}
+
+impl<T> ClhMutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self::with_relax(data)
+    }
+}
+
+impl<T, R> ClhMutex<T, R> {
+    /// 使用指定的等待策略创建CLH锁
+    pub const fn with_relax(data: T) -> Self {
+        ClhMutex {
+            raw: RawClh::with_relax(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized, R> ClhMutex<T, R> {
+    /// 锁是否被持有(或有线程在排队)，只用于调试，返回值可能立即过时
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// 非阻塞地上锁，只有在队列为空时才会成功
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::ClhMutex;
+    ///
+    /// let locked = ClhMutex::new(1);
+    /// let lock_guard = locked.try_lock();
+    /// assert!(lock_guard.is_some());
+    /// assert!(locked.try_lock().is_none());
+    /// ```
+    #[inline]
+    pub fn try_lock(&self) -> Option<ClhMutexGuard<'_, T>> {
+        self.raw.try_acquire().map(|node| self.guard(node))
+    }
+
+    #[inline]
+    fn guard(&self, node: *mut ClhNode) -> ClhMutexGuard<'_, T> {
+        ClhMutexGuard {
+            tail: &self.raw.tail,
+            node,
+            data: self.data.get(),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized, R: Relax> ClhMutex<T, R> {
+    /// 上锁
+    ///# Examle
+    /// ```
+    /// use xx_mutex_lock::ClhMutex;
+    ///
+    /// let locked = ClhMutex::new(1);
+    /// let mut lock_guard = locked.lock();
+    /// *lock_guard += 1;
+    /// assert_eq!(*lock_guard, 2)
+    /// ```
+    pub fn lock(&self) -> ClhMutexGuard<'_, T> {
+        self.guard(self.raw.acquire())
+    }
+}
+
+impl<'a, T: ?Sized> Deref for ClhMutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for ClhMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for ClhMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { ClhNode::release(self.tail, self.node) }
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawMutex for RawClh<R> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = RawClh::with_relax();
+
+    //持有者的节点保存在锁里，解锁不依赖上锁的线程
+    type GuardMarker = ::lock_api::GuardSend;
+
+    #[inline]
+    fn lock(&self) {
+        RawClh::lock(self)
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        RawClh::try_lock(self)
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        RawClh::unlock(self)
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        RawClh::is_locked(self)
+    }
+}
+
+/// CLH锁解锁时总是把锁交给队列中的下一个节点，本身就是公平的
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawMutexFair for RawClh<R> {
+    #[inline]
+    unsafe fn unlock_fair(&self) {
+        RawClh::unlock(self)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::clh_mutex::ClhMutex;
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn test() {
+        let lock = Arc::new(ClhMutex::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *lock.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*lock.lock(), 400);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn test_try_lock() {
+        let lock = ClhMutex::new(1);
+        let guard = lock.try_lock();
+        assert!(guard.is_some());
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+        assert!(lock.try_lock().is_some());
+        assert!(!lock.is_locked());
+    }
+}
@@ -0,0 +1,39 @@
+//! 诊断功能使用的时钟，只在`watchdog`或`stats` feature下可用
+//!
+//! 锁本身不依赖时间，需要计时的诊断功能（[`crate::watchdog`]的时间预算、
+//! `stats`的等待和持有时间）通过[`set_clock`]注册的函数读取当前时间，
+//! 单位由时钟决定，例如纳秒或者CPU周期。没有设置时钟时不计时
+//! # Example
+//! ```
+//! use std::time::Instant;
+//! use xx_mutex_lock::clock;
+//!
+//! fn now() -> u64 {
+//!     static START: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+//!     START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+//! }
+//!
+//! clock::set_clock(Some(now));
+//! ```
+use core::{
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+static CLOCK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// 设置时钟，None表示不计时
+pub fn set_clock(clock: Option<fn() -> u64>) {
+    CLOCK.store(
+        clock.map_or(ptr::null_mut(), |c| c as *mut ()),
+        Ordering::Release,
+    );
+}
+
+/// 当前时间，没有设置时钟时返回None
+#[inline]
+pub(crate) fn now() -> Option<u64> {
+    let clock = CLOCK.load(Ordering::Acquire);
+    //只会存入set_clock中的函数指针
+    (!clock.is_null()).then(|| unsafe { core::mem::transmute::<*mut (), fn() -> u64>(clock) }())
+}
@@ -0,0 +1,317 @@
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "std")]
+use std::{collections::VecDeque, sync::Mutex as StdMutex, thread::Thread};
+
+use crate::mutex::MutexGuard;
+#[cfg(feature = "std")]
+use crate::poison::{self, LockResult};
+use crate::relax::{Relax, Spin};
+
+///
+/// 条件变量，和[`Mutex`](crate::Mutex)配合使用
+/// 等待时解开守卫对应的锁，被通知后重新上锁再返回
+/// # Exapmle
+///
+/// ```
+/// use xx_mutex_lock::{Condvar, Mutex};
+///
+/// let pair = (Mutex::new(false), Condvar::new());
+/// std::thread::scope(|s| {
+///     s.spawn(|| {
+/// #       #[cfg(feature = "std")]
+/// #       let mut started = pair.0.lock().unwrap();
+/// #       #[cfg(not(feature = "std"))]
+///         let mut started = pair.0.lock();
+///         *started = true;
+///         pair.1.notify_one();
+///     });
+/// #   #[cfg(feature = "std")]
+/// #   let started = pair.0.lock().unwrap();
+/// #   #[cfg(not(feature = "std"))]
+///     let started = pair.0.lock();
+/// #   #[cfg(feature = "std")]
+/// #   let started = pair.1.wait_while(started, |started| !*started).unwrap();
+/// #   #[cfg(not(feature = "std"))]
+///     let started = pair.1.wait_while(started, |started| !*started);
+///     assert!(*started);
+/// });
+/// ```
+///
+/// 等待通知时按照类型参数`R`的策略自旋，参见[`crate::relax`]。
+/// 在`std` feature下，自旋一段时间仍没有通知时会park当前线程，由`notify_*`唤醒
+///
+/// 等待之前守卫按照正常的流程释放，被通知后和[`Mutex::lock`](crate::Mutex::lock)一样重新上锁，
+/// 使用锁自己的等待策略，等待期间持有者、`lockdep`等记录里都没有这把锁
+///
+/// 和标准库一样，`wait`可能虚假唤醒，需要在循环中检查条件，或者使用`wait_while`。
+/// 没有`std`时`notify_one`会让所有正在等待的线程返回
+pub struct Condvar<R = Spin> {
+    /// 每次通知加一，等待的线程看到变化后返回
+    seq: AtomicUsize,
+    /// 登记过的等待线程，按登记的顺序排列。一个线程同时只会等待一个条件变量，
+    /// 所以不会重复登记
+    #[cfg(feature = "std")]
+    waiters: StdMutex<VecDeque<Thread>>,
+    _relax: PhantomData<R>,
+}
+
+/// park之前自旋检查通知的次数
+#[cfg(feature = "std")]
+const SPIN_LIMIT: usize = 64;
+
+impl Condvar {
+    loom_const_fn! {
+        pub const fn new() -> Self {
+            Self::with_relax()
+        }
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> Condvar<R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建条件变量
+        pub const fn with_relax() -> Self {
+            Condvar {
+                seq: AtomicUsize::new(0),
+                #[cfg(feature = "std")]
+                waiters: StdMutex::new(VecDeque::new()),
+                _relax: PhantomData,
+            }
+        }
+    }
+
+    /// 唤醒至少一个等待的线程
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        #[cfg(feature = "std")]
+        if let Some(thread) = self.waiters().pop_front() {
+            thread.unpark();
+        }
+    }
+
+    /// 唤醒所有等待的线程
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        #[cfg(feature = "std")]
+        for thread in core::mem::take(&mut *self.waiters()) {
+            thread.unpark();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn waiters(&self) -> std::sync::MutexGuard<'_, VecDeque<Thread>> {
+        //持有时不会panic，不需要处理中毒
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R: Relax> Condvar<R> {
+    /// 解锁并等待通知，返回前重新上锁
+    #[cfg(not(feature = "std"))]
+    pub fn wait<'a, T: ?Sized, M: Relax>(
+        &self,
+        guard: MutexGuard<'a, T, M>,
+    ) -> MutexGuard<'a, T, M> {
+        self.wait_guard(guard)
+    }
+
+    /// 解锁并等待通知，返回前重新上锁，锁已中毒时返回`Err`
+    #[cfg(feature = "std")]
+    pub fn wait<'a, T: ?Sized, M: Relax>(
+        &self,
+        guard: MutexGuard<'a, T, M>,
+    ) -> LockResult<MutexGuard<'a, T, M>> {
+        let guard = self.wait_guard(guard);
+        poison::map_result(MutexGuard::is_poisoned(&guard), guard)
+    }
+
+    /// `condition`返回true时一直等待，返回的守卫保证`condition`为false
+    #[cfg(not(feature = "std"))]
+    pub fn wait_while<'a, T: ?Sized, M: Relax, F>(
+        &self,
+        guard: MutexGuard<'a, T, M>,
+        condition: F,
+    ) -> MutexGuard<'a, T, M>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        self.wait_while_guard(guard, condition)
+    }
+
+    /// `condition`返回true时一直等待，返回的守卫保证`condition`为false，锁已中毒时返回`Err`
+    #[cfg(feature = "std")]
+    pub fn wait_while<'a, T: ?Sized, M: Relax, F>(
+        &self,
+        guard: MutexGuard<'a, T, M>,
+        condition: F,
+    ) -> LockResult<MutexGuard<'a, T, M>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let guard = self.wait_while_guard(guard, condition);
+        poison::map_result(MutexGuard::is_poisoned(&guard), guard)
+    }
+
+    fn wait_while_guard<'a, T: ?Sized, M: Relax, F>(
+        &self,
+        mut guard: MutexGuard<'a, T, M>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T, M>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait_guard(guard);
+        }
+        guard
+    }
+
+    fn wait_guard<'a, T: ?Sized, M: Relax>(
+        &self,
+        guard: MutexGuard<'a, T, M>,
+    ) -> MutexGuard<'a, T, M> {
+        //在解锁之前读取，解锁之后的通知一定能被看到
+        let seq = self.seq.load(Ordering::SeqCst);
+        #[cfg(feature = "std")]
+        let current = std::thread::current();
+        #[cfg(feature = "std")]
+        self.waiters().push_back(current.clone());
+
+        let guard = MutexGuard::unlocked(guard, || self.wait_seq(seq));
+
+        //被通知时已经移出队列，只有自己看到通知返回时还在
+        #[cfg(feature = "std")]
+        self.waiters().retain(|thread| thread.id() != current.id());
+        guard
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn wait_seq(&self, seq: usize) {
+        let mut relax = R::default();
+        while self.seq.load(Ordering::Acquire) == seq {
+            relax.relax();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn wait_seq(&self, seq: usize) {
+        let mut relax = R::default();
+        for _ in 0..SPIN_LIMIT {
+            if self.seq.load(Ordering::Acquire) != seq {
+                return;
+            }
+            relax.relax();
+        }
+        //通知会unpark登记过的线程，park之前的通知也不会丢失
+        while self.seq.load(Ordering::Acquire) == seq {
+            std::thread::park();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::test_util::unpoison;
+    use crate::{Condvar, Mutex};
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_notify_one() {
+        let pair = Arc::new((Mutex::new(false), Condvar::new()));
+        let pair2 = pair.clone();
+        let t = std::thread::spawn(move || {
+            *unpoison(pair2.0.lock()) = true;
+            pair2.1.notify_one();
+        });
+
+        let (lock, cvar) = &*pair;
+        let mut started = unpoison(lock.lock());
+        while !*started {
+            started = unpoison(cvar.wait(started));
+        }
+        drop(started);
+        t.join().expect("err");
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_relock_stats() {
+        //重新上锁走完整的上锁流程，会被统计为一次获取
+        let pair = Arc::new((Mutex::new(false), Condvar::new()));
+        let pair2 = pair.clone();
+        let (lock, cvar) = &*pair;
+        let mut started = unpoison(lock.lock());
+        let t = std::thread::spawn(move || {
+            *unpoison(pair2.0.lock()) = true;
+            pair2.1.notify_one();
+        });
+        while !*started {
+            started = unpoison(cvar.wait(started));
+        }
+        drop(started);
+        t.join().expect("err");
+        //主线程和通知线程各一次，加上至少一次重新上锁
+        assert!(lock.stats().acquisitions >= 3);
+    }
+
+    #[test]
+    fn test_notify_all() {
+        let pair = Arc::new((Mutex::new(0), Condvar::new()));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let pair = pair.clone();
+                std::thread::spawn(move || {
+                    let (lock, cvar) = &*pair;
+                    let mut count = unpoison(cvar.wait_while(unpoison(lock.lock()), |n| *n == 0));
+                    *count += 1;
+                })
+            })
+            .collect();
+
+        let (lock, cvar) = &*pair;
+        *unpoison(lock.lock()) = 1;
+        cvar.notify_all();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*unpoison(lock.lock()), 5);
+    }
+
+    #[test]
+    fn test_notify_all_many() {
+        //等待的线程比较多时，每个线程都要能被notify_all唤醒
+        let pair = Arc::new((Mutex::new(false), Condvar::new()));
+        let threads: Vec<_> = (0..16)
+            .map(|_| {
+                let pair = pair.clone();
+                std::thread::spawn(move || {
+                    let (lock, cvar) = &*pair;
+                    drop(unpoison(
+                        cvar.wait_while(unpoison(lock.lock()), |ready| !*ready),
+                    ));
+                })
+            })
+            .collect();
+
+        let (lock, cvar) = &*pair;
+        //等所有线程都开始park
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        *unpoison(lock.lock()) = true;
+        cvar.notify_all();
+        for t in threads {
+            t.join().expect("err");
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! 基于等待图的死锁检测，只在`deadlock_detection` feature下可用
+//!
+//! [`Mutex`](crate::Mutex)和[`RWLock`](crate::RWLock)会记录每个锁的持有者，
+//! 以及正在`lock`/`read`/`write`里等待的线程。
+//! 等待某个锁的线程指向这个锁的所有持有者，[`check_deadlock`]在这张图里查找环，
+//! 环上的线程都在等待下一个线程持有的锁，永远不会醒来。
+//!
+//! 线程用[`ThreadOwner`]标识，锁用内部原子变量的地址标识
+//! # Example
+//! ```
+//! use xx_mutex_lock::check_deadlock;
+//!
+//! //在监控线程里定期检查
+//! for cycle in check_deadlock() {
+//!     for waiter in cycle {
+//!         println!("{:?} waits for lock {:#x}", waiter.owner, waiter.lock);
+//!     }
+//! }
+//! ```
+use core::num::NonZeroUsize;
+use std::{
+    sync::{Mutex as StdMutex, PoisonError},
+    vec::Vec,
+};
+
+use crate::owner::{OwnerId, ThreadOwner};
+
+/// 环上的一个等待者，它等待的锁被环上的下一个等待者持有
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Waiter {
+    /// 等待者的标识，参见[`ThreadOwner`]
+    pub owner: NonZeroUsize,
+    /// 等待的锁的地址
+    pub lock: usize,
+}
+
+struct State {
+    /// (锁, 持有者)，读锁可以有多个持有者
+    holders: Vec<(usize, NonZeroUsize)>,
+    /// (等待者, 锁)，每个等待者同时只会等待一个锁
+    waiters: Vec<(NonZeroUsize, usize)>,
+}
+
+static STATE: StdMutex<State> = StdMutex::new(State {
+    holders: Vec::new(),
+    waiters: Vec::new(),
+});
+
+#[inline]
+fn addr<L: ?Sized>(lock: &L) -> usize {
+    lock as *const L as *const () as usize
+}
+
+fn with<U>(f: impl FnOnce(&mut State) -> U) -> U {
+    f(&mut STATE.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// 正在等待锁的记录，drop时移除
+/// 等待时看门狗的回调可能panic，这时记录也会在栈展开时移除
+pub(crate) struct Waiting {
+    owner: NonZeroUsize,
+    lock: usize,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        let waiter = (self.owner, self.lock);
+        with(|state| {
+            if let Some(i) = state.waiters.iter().position(|&w| w == waiter) {
+                state.waiters.swap_remove(i);
+            }
+        });
+    }
+}
+
+/// 开始阻塞地等待锁，拿到锁之后drop返回的记录
+pub(crate) fn wait<L: ?Sized>(lock: &L) -> Waiting {
+    let waiting = Waiting {
+        owner: ThreadOwner::current(),
+        lock: addr(lock),
+    };
+    with(|state| state.waiters.push((waiting.owner, waiting.lock)));
+    waiting
+}
+
+/// 拿到锁之后调用
+pub(crate) fn acquired<L: ?Sized>(lock: &L) {
+    let owner = ThreadOwner::current();
+    with(|state| state.holders.push((addr(lock), owner)));
+}
+
+/// 释放锁时调用，守卫可能在别的线程drop，找不到当前线程时移除任意一个持有者
+pub(crate) fn release<L: ?Sized>(lock: &L) {
+    let owner = ThreadOwner::current();
+    let lock = addr(lock);
+    with(|state| {
+        let i = state
+            .holders
+            .iter()
+            .position(|&h| h == (lock, owner))
+            .or_else(|| state.holders.iter().position(|&(l, _)| l == lock));
+        if let Some(i) = i {
+            state.holders.swap_remove(i);
+        }
+    });
+}
+
+/// 查找当前的所有死锁
+/// 每个环从标识最小的等待者开始，同一个环只返回一次
+pub fn check_deadlock() -> Vec<Vec<Waiter>> {
+    let (holders, waiters) = with(|state| (state.holders.clone(), state.waiters.clone()));
+    let mut cycles = Vec::new();
+    for &(start, lock) in &waiters {
+        let mut path = Vec::new();
+        path.push(Waiter { owner: start, lock });
+        search(&holders, &waiters, start, &mut path, &mut cycles);
+    }
+    cycles
+}
+
+fn search(
+    holders: &[(usize, NonZeroUsize)],
+    waiters: &[(NonZeroUsize, usize)],
+    start: NonZeroUsize,
+    path: &mut Vec<Waiter>,
+    cycles: &mut Vec<Vec<Waiter>>,
+) {
+    let lock = path[path.len() - 1].lock;
+    let current = path[path.len() - 1].owner;
+    for &(_, holder) in holders.iter().filter(|&&(l, _)| l == lock) {
+        //自己等待自己持有的锁也是死锁
+        if holder == start {
+            if !cycles.contains(path) {
+                cycles.push(path.clone());
+            }
+            continue;
+        }
+        //只从最小的标识开始，避免同一个环的不同旋转
+        if holder < start || holder == current || path.iter().any(|w| w.owner == holder) {
+            continue;
+        }
+        if let Some(&(_, next)) = waiters.iter().find(|&&(w, _)| w == holder) {
+            path.push(Waiter {
+                owner: holder,
+                lock: next,
+            });
+            search(holders, waiters, start, path, cycles);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::deadlock::{self, check_deadlock};
+    use crate::owner::{OwnerId, ThreadOwner};
+    use crate::relax::Yield;
+    use crate::test_util::unpoison;
+    use crate::Mutex;
+    use std::sync::{mpsc, Arc, Barrier};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_deadlock() {
+        //在同一个位置创建，lockdep会把它们当成同一类，不检查顺序
+        let new = || Arc::new(Mutex::<i32, Yield>::with_relax(0));
+        let (a, b) = (new(), new());
+        let barrier = Arc::new(Barrier::new(2));
+        //t1把a的守卫交给主线程，主线程释放它来解开死锁
+        let (sender, receiver) = mpsc::channel();
+        let (t1_a, t1_b, t1_barrier) = (a.clone(), b.clone(), barrier.clone());
+        let t1 = std::thread::spawn(move || {
+            sender.send(unpoison(t1_a.lock_arc())).expect("err");
+            t1_barrier.wait();
+            let _b = unpoison(t1_b.lock());
+        });
+        let (t2_a, t2_b) = (a.clone(), b.clone());
+        let t2 = std::thread::spawn(move || {
+            let _b = unpoison(t2_b.lock());
+            barrier.wait();
+            let _a = unpoison(t2_a.lock());
+        });
+        let a_guard = receiver.recv().expect("err");
+
+        let start = Instant::now();
+        let cycle = loop {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "deadlock not found"
+            );
+            let cycles = check_deadlock();
+            let found = cycles.into_iter().find(|cycle| {
+                cycle.len() == 2
+                    && cycle
+                        .iter()
+                        .any(|w| w.lock == &a.raw.lock as *const _ as usize)
+                    && cycle
+                        .iter()
+                        .any(|w| w.lock == &b.raw.lock as *const _ as usize)
+            });
+            if let Some(cycle) = found {
+                break cycle;
+            }
+            std::thread::yield_now();
+        };
+        assert_ne!(cycle[0].owner, cycle[1].owner);
+
+        //释放a之后t2先结束，然后t1拿到b
+        drop(a_guard);
+        t1.join().expect("err");
+        t2.join().expect("err");
+        assert!(check_deadlock().iter().all(|cycle| cycle
+            .iter()
+            .all(|w| w.lock != &a.raw.lock as *const _ as usize)));
+    }
+
+    #[test]
+    fn test_wait_unwind() {
+        //等待时panic，栈展开时移除等待记录
+        let lock = Mutex::new(0);
+        let res = std::panic::catch_unwind(|| {
+            let _waiting = deadlock::wait(&lock.raw.lock);
+            panic!("panic while waiting");
+        });
+        assert!(res.is_err());
+        let owner = ThreadOwner::current();
+        assert!(deadlock::with(|state| state
+            .waiters
+            .iter()
+            .all(|&(w, _)| w != owner)));
+    }
+}
@@ -1,29 +1,84 @@
 use core::{cell::Cell, ops::Deref};
 
 use super::once_lock::OnceLock;
+use crate::relax::{Relax, Spin};
 
 ///
 /// 将初始化推迟到第一次访问的时候再初始化
 /// # Example
 /// ```
+///  use xx_mutex_lock::LazyLock;
+///
 ///  let lazy = LazyLock::new(|| 1 + 3);
-///  let a = **lazy;
+///  let a = *lazy;
 ///  std::println!("I am {}", a);
 /// ```
-pub struct LazyLock<T, F = fn() -> T> {
-    cell: OnceLock<T>,
+///
+/// 等待其他线程初始化时的行为由类型参数`R`决定，默认为[`Spin`]
+///
+/// 初始化函数再次访问同一个LazyLock时会panic，参见[`OnceLock`]
+pub struct LazyLock<T, F = fn() -> T, R = Spin> {
+    cell: OnceLock<T, R>,
     init: Cell<Option<F>>,
 }
 
-unsafe impl<T, F: Send> Sync for LazyLock<T, F> where OnceLock<T>: Sync {}
+unsafe impl<T, F: Send, R> Sync for LazyLock<T, F, R> where OnceLock<T, R>: Sync {}
 
 impl<T, F> LazyLock<T, F> {
-    pub const fn new(f: F) -> Self {
-        Self {
-            cell: OnceLock::new(),
-            init: Cell::new(Some(f)),
+    loom_const_fn! {
+        pub const fn new(f: F) -> Self {
+            Self::with_relax(f)
         }
     }
+
+    loom_const_fn! {
+        /// 创建带名字的LazyLock，名字只用于诊断输出，参见[`Mutex::named`](crate::Mutex::named)
+        /// # Example
+        /// ```
+        /// use xx_mutex_lock::LazyLock;
+        ///
+        /// static TABLE: LazyLock<[u8; 4]> = LazyLock::named("table", || [1, 2, 3, 4]);
+        /// assert_eq!(TABLE[3], 4);
+        /// ```
+        pub const fn named(name: &'static str, f: F) -> Self {
+            Self {
+                cell: OnceLock::named(name),
+                init: Cell::new(Some(f)),
+            }
+        }
+    }
+}
+
+impl<T, F, R> LazyLock<T, F, R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建LazyLock
+        /// # Example
+        /// ```
+        /// use xx_mutex_lock::{Backoff, LazyLock};
+        ///
+        /// let lazy: LazyLock<i32, _, Backoff> = LazyLock::with_relax(|| 1 + 3);
+        /// assert_eq!(4, *lazy);
+        /// ```
+        pub const fn with_relax(f: F) -> Self {
+            Self {
+                cell: OnceLock::with_relax(),
+                init: Cell::new(Some(f)),
+            }
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.cell.get()
+    }
+
+    /// 由[`LazyLock::named`]设置的名字
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    pub fn name(&self) -> Option<&'static str> {
+        self.cell.name()
+    }
+}
+
+impl<T, F, R: Relax> LazyLock<T, F, R> {
     ///
     /// 在解引用的时候调用force初始化
     /// 这样就可以达到在访问的时候初始化
@@ -32,18 +87,18 @@ impl<T, F> LazyLock<T, F> {
     where
         F: FnOnce() -> T,
     {
-        this.cell.get_or_init(|| match this.init.take() {
-            Some(f) => f(),
-            None => panic!("Lazy instance has previously been poisoned"),
-        })
-    }
-
-    pub fn get(&self) -> Option<&T> {
-        self.cell.get()
+        if this.cell.get().is_none() {
+            this.cell.check_reentrant("LazyLock", this);
+        }
+        this.cell
+            .get_or_init_as("LazyLock", || match this.init.take() {
+                Some(f) => f(),
+                None => panic!("Lazy instance has previously been poisoned"),
+            })
     }
 }
 ///在解引用的时候调用force初始化
-impl<T, F: FnOnce() -> T> Deref for LazyLock<T, F> {
+impl<T, F: FnOnce() -> T, R: Relax> Deref for LazyLock<T, F, R> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         Self::force(self)
@@ -83,4 +138,19 @@ pub mod test {
         let c = **arc_once;
         std::println!("{}", c);
     }
+
+    //loom下构造函数不是const，不能初始化静态变量
+    #[cfg(not(loom))]
+    #[test]
+    fn test_reentrant_init() {
+        use std::panic::catch_unwind;
+        static LAZY: LazyLock<i32> = LazyLock::new(|| *LAZY + 1);
+        crate::test_util::track_owner();
+        let res = catch_unwind(|| *LAZY);
+        let msg = *res
+            .expect_err("err")
+            .downcast::<std::string::String>()
+            .expect("err");
+        assert!(msg.starts_with("reentrant init: LazyLock<i32>"));
+    }
 }
@@ -0,0 +1,320 @@
+//! 编译期的锁层级
+//!
+//! [`LeveledMutex`]和[`LeveledRWLock`]带有一个常量层级`LEVEL`，
+//! 上锁时需要传入[`LevelToken`]，证明当前只持有层级更低的锁，
+//! 同时得到一个`LEVEL`层级的令牌，用来获取更高层级的锁。
+//! 层级之间的顺序由[`Below`]约束表示，按相反的顺序上锁在类型检查时就会报错，
+//! 因此不会出现加锁顺序反转。锁的层级范围是1到31
+//! # Example
+//! ```
+//! use xx_mutex_lock::leveled::{LevelToken, LeveledMutex, LeveledRWLock};
+//!
+//! static CONFIG: LeveledRWLock<i32, 1> = LeveledRWLock::new(1);
+//! static COUNTER: LeveledMutex<i32, 2> = LeveledMutex::new(0);
+//!
+//! //这是线程入口处唯一的根令牌
+//! let mut root = unsafe { LevelToken::root() };
+//! # #[cfg(feature = "std")]
+//! # let (config, mut token) = CONFIG.read(&mut root).unwrap();
+//! # #[cfg(not(feature = "std"))]
+//! let (config, mut token) = CONFIG.read(&mut root);
+//! # #[cfg(feature = "std")]
+//! # let (mut counter, _) = COUNTER.lock(&mut token).unwrap();
+//! # #[cfg(not(feature = "std"))]
+//! let (mut counter, _) = COUNTER.lock(&mut token);
+//! *counter += *config;
+//! ```
+//! 反过来先持有层级2的锁再获取层级1的锁，无法通过编译
+//! ```compile_fail
+//! use xx_mutex_lock::leveled::{LevelToken, LeveledMutex, LeveledRWLock};
+//!
+//! static CONFIG: LeveledRWLock<i32, 1> = LeveledRWLock::new(1);
+//! static COUNTER: LeveledMutex<i32, 2> = LeveledMutex::new(0);
+//!
+//! let mut root = unsafe { LevelToken::root() };
+//! let (_counter, mut token) = COUNTER.try_lock(&mut root).unwrap();
+//! let _config = CONFIG.try_read(&mut token);
+//! ```
+use core::marker::PhantomData;
+
+use crate::mutex::{Mutex, MutexGuard};
+#[cfg(feature = "std")]
+use crate::poison::{LockResult, PoisonError};
+use crate::relax::{Relax, Spin};
+use crate::rw_lock::{RWLock, RWLockReadGuard, RWLockWriteGuard};
+
+/// 层级`N`的标记类型
+pub struct Level<const N: u32>;
+
+/// `Self`的层级低于`L`，只为0到31之间的层级实现
+#[diagnostic::on_unimplemented(
+    message = "cannot acquire a lock of `{L}` while holding `{Self}`",
+    label = "lock levels must be acquired in increasing order"
+)]
+pub trait Below<L> {}
+
+macro_rules! below {
+    ($low:literal $(, $high:literal)*) => {
+        $(impl Below<Level<$high>> for Level<$low> {})*
+        below!($($high),*);
+    };
+    () => {};
+}
+
+below!(
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+    26, 27, 28, 29, 30, 31
+);
+
+/// 层级令牌，持有`LEVEL`层级的令牌说明当前最多持有`LEVEL`层级的锁
+///
+/// 令牌不能跨线程传递，借用的生命周期保证上层的令牌在下层的令牌存在期间不能使用
+pub struct LevelToken<'a, const LEVEL: u32> {
+    _marker: PhantomData<(&'a mut (), *mut ())>,
+}
+
+impl LevelToken<'static, 0> {
+    /// 创建根令牌，表示没有持有任何带层级的锁
+    /// # Safety
+    /// 调用者保证当前线程没有持有带层级的锁，并且同一时刻只有这一个根令牌，
+    /// 通常在线程的入口处创建一次。否则新的根令牌可以绕过层级检查
+    pub unsafe fn root() -> Self {
+        LevelToken {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, const LEVEL: u32> LevelToken<'a, LEVEL> {
+    #[inline]
+    fn next<const NEXT: u32>(&mut self) -> LevelToken<'_, NEXT>
+    where
+        Level<LEVEL>: Below<Level<NEXT>>,
+    {
+        LevelToken {
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// 带层级的互斥锁，参见[模块文档](self)
+pub struct LeveledMutex<T: ?Sized, const LEVEL: u32, R = Spin> {
+    inner: Mutex<T, R>,
+}
+
+/// 带层级的读写锁，参见[模块文档](self)
+pub struct LeveledRWLock<T: ?Sized, const LEVEL: u32, R = Spin> {
+    inner: RWLock<T, R>,
+}
+
+impl<T, const LEVEL: u32> LeveledMutex<T, LEVEL> {
+    loom_const_fn! {
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn new(data: T) -> Self {
+            Self::with_relax(data)
+        }
+    }
+}
+
+impl<T, const LEVEL: u32, R> LeveledMutex<T, LEVEL, R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建互斥锁
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn with_relax(data: T) -> Self {
+            LeveledMutex {
+                inner: Mutex::with_relax(data),
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, const LEVEL: u32, R> LeveledMutex<T, LEVEL, R> {
+    /// 非阻塞地上锁，成功时同时返回`LEVEL`层级的令牌
+    #[inline]
+    pub fn try_lock<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> Option<(MutexGuard<'a, T, R>, LevelToken<'a, LEVEL>)>
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        self.inner.try_lock().map(|guard| (guard, next))
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: ?Sized, const LEVEL: u32, R: Relax> LeveledMutex<T, LEVEL, R> {
+    /// 上锁，同时返回`LEVEL`层级的令牌
+    #[cfg(not(feature = "std"))]
+    pub fn lock<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> (MutexGuard<'a, T, R>, LevelToken<'a, LEVEL>)
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        (self.inner.lock(), next)
+    }
+
+    /// 上锁，同时返回`LEVEL`层级的令牌，锁中毒时返回[`PoisonError`]
+    #[cfg(feature = "std")]
+    pub fn lock<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> LockResult<(MutexGuard<'a, T, R>, LevelToken<'a, LEVEL>)>
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        match self.inner.lock() {
+            Ok(guard) => Ok((guard, next)),
+            Err(err) => Err(PoisonError::new((err.into_inner(), next))),
+        }
+    }
+}
+
+impl<T, const LEVEL: u32> LeveledRWLock<T, LEVEL> {
+    loom_const_fn! {
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn new(data: T) -> Self {
+            Self::with_relax(data)
+        }
+    }
+}
+
+impl<T, const LEVEL: u32, R> LeveledRWLock<T, LEVEL, R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建读写锁
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn with_relax(data: T) -> Self {
+            LeveledRWLock {
+                inner: RWLock::with_relax(data),
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, const LEVEL: u32, R> LeveledRWLock<T, LEVEL, R> {
+    /// 非阻塞地获取读锁，成功时同时返回`LEVEL`层级的令牌
+    #[inline]
+    pub fn try_read<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> Option<(RWLockReadGuard<'a, T>, LevelToken<'a, LEVEL>)>
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        self.inner.try_read().map(|guard| (guard, next))
+    }
+
+    /// 非阻塞地获取写锁，成功时同时返回`LEVEL`层级的令牌
+    #[inline]
+    pub fn try_write<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> Option<(RWLockWriteGuard<'a, T>, LevelToken<'a, LEVEL>)>
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        self.inner.try_write().map(|guard| (guard, next))
+    }
+}
+
+impl<T: ?Sized, const LEVEL: u32, R: Relax> LeveledRWLock<T, LEVEL, R> {
+    /// 获取读锁，同时返回`LEVEL`层级的令牌
+    #[cfg(not(feature = "std"))]
+    pub fn read<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> (RWLockReadGuard<'a, T>, LevelToken<'a, LEVEL>)
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        (self.inner.read(), next)
+    }
+
+    /// 获取读锁，同时返回`LEVEL`层级的令牌，锁中毒时返回[`PoisonError`]
+    #[cfg(feature = "std")]
+    pub fn read<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> LockResult<(RWLockReadGuard<'a, T>, LevelToken<'a, LEVEL>)>
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        match self.inner.read() {
+            Ok(guard) => Ok((guard, next)),
+            Err(err) => Err(PoisonError::new((err.into_inner(), next))),
+        }
+    }
+
+    /// 获取写锁，同时返回`LEVEL`层级的令牌
+    #[cfg(not(feature = "std"))]
+    pub fn write<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> (RWLockWriteGuard<'a, T>, LevelToken<'a, LEVEL>)
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        (self.inner.write(), next)
+    }
+
+    /// 获取写锁，同时返回`LEVEL`层级的令牌，锁中毒时返回[`PoisonError`]
+    #[cfg(feature = "std")]
+    pub fn write<'a, const HELD: u32>(
+        &'a self,
+        token: &'a mut LevelToken<'_, HELD>,
+    ) -> LockResult<(RWLockWriteGuard<'a, T>, LevelToken<'a, LEVEL>)>
+    where
+        Level<HELD>: Below<Level<LEVEL>>,
+    {
+        let next = token.next();
+        match self.inner.write() {
+            Ok(guard) => Ok((guard, next)),
+            Err(err) => Err(PoisonError::new((err.into_inner(), next))),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::leveled::{LevelToken, LeveledMutex, LeveledRWLock};
+    use crate::test_util::unpoison;
+
+    #[test]
+    fn test_levels() {
+        let config: LeveledRWLock<i32, 1> = LeveledRWLock::new(2);
+        let counter: LeveledMutex<i32, 3> = LeveledMutex::new(0);
+
+        let mut root = unsafe { LevelToken::root() };
+        {
+            let (config, mut token) = unpoison(config.read(&mut root));
+            let (mut counter, _) = unpoison(counter.lock(&mut token));
+            *counter += *config;
+        }
+        //可以跳过中间的层级
+        {
+            let (mut counter, _) = counter.try_lock(&mut root).expect("err");
+            *counter += 1;
+        }
+        {
+            let (mut config, mut token) = config.try_write(&mut root).expect("err");
+            *config += 1;
+            let (counter, _) = counter.try_lock(&mut token).expect("err");
+            assert_eq!(*counter, 3);
+        }
+        assert_eq!(*config.try_read(&mut root).expect("err").0, 3);
+    }
+}
@@ -1,16 +1,164 @@
 #![no_std]
 #![feature(never_type)]
 #![feature(dropck_eyepatch)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "_std")]
+extern crate std;
+
+//lock_api的原始锁需要常量INIT，loom的原子类型做不到
+#[cfg(all(loom, feature = "lock_api"))]
+compile_error!("the `lock_api` feature is not supported under `cfg(loom)`");
+
+/// 声明锁的构造函数，`cfg(loom)`下loom的原子类型不能在常量里创建，这时去掉`const`
+macro_rules! loom_const_fn {
+    ($(#[$attr:meta])* $vis:vis const fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])*
+        $vis const fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$attr])*
+        $vis fn $($rest)*
+    };
+}
+
+pub mod async_mutex;
+pub mod async_rw_lock;
+#[cfg(feature = "alloc")]
+pub mod clh_mutex;
+#[cfg(any(feature = "watchdog", feature = "stats"))]
+pub mod clock;
+pub mod condvar;
+#[cfg(feature = "deadlock_detection")]
+pub mod deadlock;
 pub mod lazy_lock;
+pub mod leveled;
+#[cfg(feature = "lock_api")]
+pub mod lock_api;
+#[cfg(feature = "lockdep")]
+pub mod lockdep;
+pub mod mcs_mutex;
 pub mod mutex;
 pub mod once;
 pub mod once_lock;
+pub mod owner;
+#[cfg(feature = "std")]
+pub mod poison;
+pub mod reentrant_mutex;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod relax;
 pub mod rw_lock;
+#[cfg(feature = "stats")]
+pub mod stats;
+mod sync;
+pub mod ticket_mutex;
+#[cfg(feature = "tracing")]
+pub mod trace;
+mod waker_set;
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
 
+pub use async_mutex::AsyncMutex;
+pub use async_mutex::AsyncMutexGuard;
+pub use async_rw_lock::AsyncRWLock;
+pub use async_rw_lock::AsyncRWLockReadGuard;
+pub use async_rw_lock::AsyncRWLockWriteGuard;
+#[cfg(feature = "alloc")]
+pub use clh_mutex::ClhMutex;
+#[cfg(feature = "alloc")]
+pub use clh_mutex::ClhMutexGuard;
+#[cfg(feature = "alloc")]
+pub use clh_mutex::RawClh;
+pub use condvar::Condvar;
+#[cfg(feature = "deadlock_detection")]
+pub use deadlock::check_deadlock;
+#[cfg(feature = "deadlock_detection")]
+pub use deadlock::Waiter;
 pub use lazy_lock::LazyLock;
+pub use leveled::Below;
+pub use leveled::Level;
+pub use leveled::LevelToken;
+pub use leveled::LeveledMutex;
+pub use leveled::LeveledRWLock;
+#[cfg(feature = "lockdep")]
+pub use lockdep::Inversion;
+#[cfg(feature = "lockdep")]
+pub use lockdep::LockClass;
+pub use mcs_mutex::McsMutex;
+pub use mcs_mutex::McsMutexGuard;
+pub use mcs_mutex::McsNode;
+#[cfg(feature = "alloc")]
+pub use mutex::ArcMutexGuard;
+pub use mutex::MappedMutexGuard;
 pub use mutex::Mutex;
 pub use mutex::MutexGuard;
+pub use mutex::RawSpin;
 pub use once_lock::OnceLock;
+pub use owner::set_owner_provider;
+pub use owner::OwnerId;
+#[cfg(feature = "_std")]
+pub use owner::ThreadOwner;
+#[cfg(feature = "std")]
+pub use poison::LockResult;
+#[cfg(feature = "std")]
+pub use poison::PoisonError;
+pub use reentrant_mutex::ReentrantMutex;
+pub use reentrant_mutex::ReentrantMutexGuard;
+#[cfg(feature = "registry")]
+pub use registry::dump_locks;
+pub use relax::Backoff;
+pub use relax::Relax;
+pub use relax::Spin;
+#[cfg(feature = "_std")]
+pub use relax::Yield;
+#[cfg(feature = "alloc")]
+pub use rw_lock::ArcRWLockReadGuard;
+#[cfg(feature = "alloc")]
+pub use rw_lock::ArcRWLockWriteGuard;
+pub use rw_lock::MappedRWLockReadGuard;
+pub use rw_lock::MappedRWLockWriteGuard;
 pub use rw_lock::RWLock;
 pub use rw_lock::RWLockReadGuard;
 pub use rw_lock::RWLockWriteGuard;
+pub use rw_lock::RawRwSpin;
+#[cfg(feature = "stats")]
+pub use stats::Histograms;
+#[cfg(feature = "stats")]
+pub use stats::Stats;
+pub use ticket_mutex::RawTicket;
+pub use ticket_mutex::TicketMutex;
+pub use ticket_mutex::TicketMutexGuard;
+
+/// 测试辅助：`std` feature下`lock`/`read`/`write`返回`LockResult`，
+/// 测试代码统一用这个函数取出守卫，这样两种配置下都能编译
+#[cfg(test)]
+pub(crate) mod test_util {
+    extern crate std;
+
+    #[cfg(feature = "std")]
+    pub fn unpoison<G>(result: crate::poison::LockResult<G>) -> G {
+        result.expect("lock poisoned")
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn unpoison<G>(guard: G) -> G {
+        guard
+    }
+
+    /// 测试用的线程标识，不依赖`std` feature
+    pub struct TestOwner;
+
+    impl crate::OwnerId for TestOwner {
+        fn current() -> core::num::NonZeroUsize {
+            std::thread_local!(static KEY: u8 = const { 0 });
+            KEY.with(|key| core::num::NonZeroUsize::new(key as *const u8 as usize).expect("err"))
+        }
+    }
+
+    /// 让调试构建下的持有者记录在没有`std` feature时也能工作
+    pub fn track_owner() {
+        crate::set_owner_provider::<TestOwner>();
+    }
+}
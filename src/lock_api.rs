@@ -0,0 +1,149 @@
+//! 基于[`lock_api`](::lock_api)的锁类型，只在`lock_api` feature下可用
+//!
+//! [`RawSpin`]、[`RawTicket`]、`RawClh`(需要`alloc` feature)和[`RawRwSpin`]实现了`lock_api`的原始锁trait，
+//! 可以直接用在对`lock_api`泛型的代码里，比较不同的锁时只需要换掉原始锁的类型。
+//! 这里的类型别名使用默认的[`Spin`](crate::Spin)等待策略，
+//! 需要其他策略时可以写成`lock_api::Mutex<RawSpin<Backoff>, T>`
+//! # Example
+//! ```
+//! use xx_mutex_lock::lock_api::{Mutex, RwLock};
+//!
+//! let locked = Mutex::new(1);
+//! *locked.lock() += 1;
+//! assert_eq!(*locked.lock(), 2);
+//!
+//! let data = RwLock::new(1);
+//! let write_lock = data.write();
+//! let read_lock = lock_api::RwLockWriteGuard::downgrade(write_lock);
+//! assert_eq!(*read_lock, 1);
+//! assert!(data.try_read().is_some());
+//! ```
+#[cfg(feature = "alloc")]
+pub use crate::clh_mutex::RawClh;
+pub use crate::mutex::RawSpin;
+pub use crate::rw_lock::RawRwSpin;
+pub use crate::ticket_mutex::RawTicket;
+
+pub type Mutex<T> = ::lock_api::Mutex<RawSpin, T>;
+pub type MutexGuard<'a, T> = ::lock_api::MutexGuard<'a, RawSpin, T>;
+pub type MappedMutexGuard<'a, T> = ::lock_api::MappedMutexGuard<'a, RawSpin, T>;
+
+pub type TicketMutex<T> = ::lock_api::Mutex<RawTicket, T>;
+pub type TicketMutexGuard<'a, T> = ::lock_api::MutexGuard<'a, RawTicket, T>;
+
+#[cfg(feature = "alloc")]
+pub type ClhMutex<T> = ::lock_api::Mutex<RawClh, T>;
+#[cfg(feature = "alloc")]
+pub type ClhMutexGuard<'a, T> = ::lock_api::MutexGuard<'a, RawClh, T>;
+
+pub type RwLock<T> = ::lock_api::RwLock<RawRwSpin, T>;
+pub type RwLockReadGuard<'a, T> = ::lock_api::RwLockReadGuard<'a, RawRwSpin, T>;
+pub type RwLockWriteGuard<'a, T> = ::lock_api::RwLockWriteGuard<'a, RawRwSpin, T>;
+pub type MappedRwLockReadGuard<'a, T> = ::lock_api::MappedRwLockReadGuard<'a, RawRwSpin, T>;
+pub type MappedRwLockWriteGuard<'a, T> = ::lock_api::MappedRwLockWriteGuard<'a, RawRwSpin, T>;
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::lock_api::{Mutex, RwLock, TicketMutex};
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_mutex() {
+        let lock = Arc::new(Mutex::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *lock.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*lock.lock(), 400);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn test_ticket_mutex() {
+        let lock = Arc::new(TicketMutex::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *lock.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*lock.lock(), 400);
+        assert!(lock.try_lock().is_some());
+        assert!(!lock.is_locked());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_clh_mutex() {
+        use crate::lock_api::ClhMutex;
+        let lock = Arc::new(ClhMutex::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *lock.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*lock.lock(), 400);
+        let guard = lock.try_lock();
+        assert!(guard.is_some());
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn test_rwlock() {
+        let data = RwLock::new(0);
+        let read_lock1 = data.read();
+        let read_lock2 = data.read();
+        assert!(data.try_write().is_none());
+        drop(read_lock1);
+        drop(read_lock2);
+
+        let mut write_lock = data.write();
+        *write_lock += 1;
+        assert!(data.is_locked_exclusive());
+        assert!(data.try_read().is_none());
+
+        let read_lock = ::lock_api::RwLockWriteGuard::downgrade(write_lock);
+        assert!(!data.is_locked_exclusive());
+        assert!(data.try_write().is_none());
+        assert_eq!(1, *data.try_read().expect("err"));
+        drop(read_lock);
+        assert!(!data.is_locked());
+    }
+
+    #[test]
+    fn test_relax() {
+        use crate::lock_api::RawSpin;
+        use crate::relax::Backoff;
+        let lock: ::lock_api::Mutex<RawSpin<Backoff>, _> = ::lock_api::Mutex::new(1);
+        *lock.lock() += 1;
+        assert_eq!(*lock.lock(), 2);
+    }
+}
@@ -0,0 +1,394 @@
+//! 运行时的加锁顺序检查，只在`lockdep` feature下可用
+//!
+//! 每个[`Mutex`](crate::Mutex)和[`RWLock`](crate::RWLock)都带有一个[`LockClass`]，
+//! 默认是创建锁的代码位置，同一位置创建的锁属于同一类。
+//! 持有A类的锁时阻塞地获取B类的锁，会在全局的顺序图里记下A -> B，
+//! 如果图里已经存在B -> ... -> A，说明两条代码路径的加锁顺序相反，
+//! 即使这次运行没有真的死锁，也会立即报告。
+//!
+//! 默认报告方式是panic，可以用[`set_inversion_handler`]换成自己的回调，
+//! 同一对锁类只报告一次。`read`和`write`按相同的方式记录。
+//!
+//! 持有的锁记在每个线程自己的列表里，守卫保存自己的那条记录，
+//! 被移动到别的线程drop时也能准确地从原来线程的列表里移除
+//! # Example
+//! ```
+//! use xx_mutex_lock::{lockdep, Mutex};
+//!
+//! fn report(inversion: &lockdep::Inversion) {
+//!     println!("{}", inversion);
+//! }
+//! lockdep::set_inversion_handler(Some(report));
+//!
+//! let a = Mutex::new(1);
+//! let b = Mutex::new(2);
+//! {
+//!     # #[cfg(feature = "std")]
+//!     # let _a = a.lock().unwrap();
+//!     # #[cfg(not(feature = "std"))]
+//!     let _a = a.lock();
+//!     # #[cfg(feature = "std")]
+//!     # let _b = b.lock().unwrap();
+//!     # #[cfg(not(feature = "std"))]
+//!     let _b = b.lock();
+//! }
+//! {
+//!     # #[cfg(feature = "std")]
+//!     # let _b = b.lock().unwrap();
+//!     # #[cfg(not(feature = "std"))]
+//!     let _b = b.lock();
+//!     # #[cfg(feature = "std")]
+//!     # let _a = a.lock().unwrap();
+//!     # #[cfg(not(feature = "std"))]
+//!     let _a = a.lock(); //调用report
+//! }
+//! ```
+use core::{
+    fmt,
+    panic::Location,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{
+    boxed::Box,
+    sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard, PoisonError},
+    vec::Vec,
+};
+
+/// 锁类，加锁顺序按锁类记录
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LockClass {
+    location: &'static Location<'static>,
+    name: Option<&'static str>,
+}
+
+/// 发现的加锁顺序反转
+#[derive(Debug, Clone)]
+pub struct Inversion {
+    /// 正在获取的锁类
+    pub acquiring: LockClass,
+    /// 当前线程已经持有的锁类
+    pub held: LockClass,
+    /// 之前观察到的顺序，从`acquiring`开始，到`held`结束
+    pub chain: Vec<LockClass>,
+}
+
+impl LockClass {
+    /// 创建一个带名字的锁类，用调用的位置区分不同的锁类
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::{lockdep::LockClass, Mutex};
+    ///
+    /// static NET: LockClass = LockClass::new("net");
+    /// let mut locked = Mutex::new(1);
+    /// locked.set_lock_class(NET);
+    /// assert_eq!(locked.lock_class().name(), Some("net"));
+    /// ```
+    #[track_caller]
+    pub const fn new(name: &'static str) -> Self {
+        LockClass {
+            location: Location::caller(),
+            name: Some(name),
+        }
+    }
+
+    /// 以调用者的位置作为锁类
+    #[track_caller]
+    pub(crate) const fn caller() -> Self {
+        LockClass {
+            location: Location::caller(),
+            name: None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+impl fmt::Display for LockClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{} ({})", name, self.location),
+            None => write!(f, "{}", self.location),
+        }
+    }
+}
+
+impl fmt::Display for Inversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lock order inversion: acquiring {} while holding {}, previously observed order: ",
+            self.acquiring, self.held
+        )?;
+        for (i, class) in self.chain.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" -> ")?;
+            }
+            write!(f, "{}", class)?;
+        }
+        Ok(())
+    }
+}
+
+/// 设置发现顺序反转时的回调，None表示恢复默认的panic
+pub fn set_inversion_handler(handler: Option<fn(&Inversion)>) {
+    *locked(&HANDLER) = handler;
+}
+
+static HANDLER: StdMutex<Option<fn(&Inversion)>> = StdMutex::new(None);
+
+static GRAPH: StdMutex<Graph> = StdMutex::new(Graph {
+    edges: Vec::new(),
+    reported: Vec::new(),
+});
+
+/// 一个线程持有的锁，(记录的编号, 锁的地址, 锁类)
+/// 只有这个线程和守卫被移动到的线程会访问，基本上没有竞争
+type Held = StdMutex<Vec<(usize, usize, LockClass)>>;
+
+/// 线程退出时已经清空的列表，留给新的线程复用
+static FREE: StdMutex<Vec<&'static Held>> = StdMutex::new(Vec::new());
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+struct Local(&'static Held);
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        //还有守卫被移动到了别的线程时，列表里的记录之后由那个守卫移除，不能交给新线程
+        if locked(self.0).is_empty() {
+            locked(&FREE).push(self.0);
+        }
+    }
+}
+
+std::thread_local! {
+    static HELD: Local = Local(locked(&FREE).pop().unwrap_or_else(|| Box::leak(Box::default())));
+}
+
+fn locked<T>(mutex: &StdMutex<T>) -> StdMutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 当前线程的列表，线程退出的过程中返回None
+fn current() -> Option<&'static Held> {
+    HELD.try_with(|local| local.0).ok()
+}
+
+/// 守卫里保存的持有记录，释放时只移除这一条
+#[derive(Clone, Copy)]
+pub(crate) struct Entry {
+    held: Option<&'static Held>,
+    id: usize,
+}
+
+/// 观察到的加锁顺序，(先, 后)
+struct Graph {
+    edges: Vec<(LockClass, LockClass)>,
+    reported: Vec<(LockClass, LockClass)>,
+}
+
+impl Graph {
+    /// 查找from到to的路径
+    fn path(&self, from: LockClass, to: LockClass) -> Option<Vec<LockClass>> {
+        let mut visited = Vec::new();
+        let mut chain = Vec::new();
+        if self.search(from, to, &mut visited, &mut chain) {
+            Some(chain)
+        } else {
+            None
+        }
+    }
+
+    fn search(
+        &self,
+        from: LockClass,
+        to: LockClass,
+        visited: &mut Vec<LockClass>,
+        chain: &mut Vec<LockClass>,
+    ) -> bool {
+        chain.push(from);
+        if from == to {
+            return true;
+        }
+        visited.push(from);
+        for &(_, next) in self.edges.iter().filter(|(prev, _)| *prev == from) {
+            if !visited.contains(&next) && self.search(next, to, visited, chain) {
+                return true;
+            }
+        }
+        chain.pop();
+        false
+    }
+}
+
+#[inline]
+fn addr<L: ?Sized>(lock: &L) -> usize {
+    lock as *const L as *const () as usize
+}
+
+/// 阻塞地获取锁之前调用，记录顺序并检查反转
+pub(crate) fn check<L: ?Sized>(lock: &L, class: LockClass) {
+    let Some(held) = current() else {
+        return;
+    };
+    let held: Vec<LockClass> = locked(held)
+        .iter()
+        .filter(|&&(_, a, _)| a != addr(lock))
+        .map(|&(_, _, c)| c)
+        .collect();
+    if held.is_empty() {
+        return;
+    }
+
+    let mut inversions = Vec::new();
+    {
+        let mut graph = locked(&GRAPH);
+        for prev in held {
+            //同一类的锁互相嵌套不做检查
+            if prev == class || graph.edges.contains(&(prev, class)) {
+                continue;
+            }
+            match graph.path(class, prev) {
+                Some(chain) => {
+                    if !graph.reported.contains(&(prev, class)) {
+                        graph.reported.push((prev, class));
+                        inversions.push(Inversion {
+                            acquiring: class,
+                            held: prev,
+                            chain,
+                        });
+                    }
+                }
+                None => graph.edges.push((prev, class)),
+            }
+        }
+    }
+    //在释放图的锁之后再报告，回调里可以继续加锁
+    for inversion in inversions {
+        report(&inversion);
+    }
+}
+
+/// 拿到锁之后调用，返回的记录保存在守卫里
+pub(crate) fn acquired<L: ?Sized>(lock: &L, class: LockClass) -> Entry {
+    let held = current();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    if let Some(held) = held {
+        locked(held).push((id, addr(lock), class));
+    }
+    Entry { held, id }
+}
+
+/// 释放锁时调用，守卫可能在别的线程drop，从拿到锁的线程的列表里移除
+pub(crate) fn release(entry: Entry) {
+    if let Some(held) = entry.held {
+        let mut held = locked(held);
+        if let Some(i) = held.iter().rposition(|&(id, _, _)| id == entry.id) {
+            held.remove(i);
+        }
+    }
+}
+
+fn report(inversion: &Inversion) {
+    let handler = *locked(&HANDLER);
+    match handler {
+        Some(handler) => handler(inversion),
+        None => panic!("{}", inversion),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::lockdep::{self, Inversion, LockClass};
+    use crate::{test_util::unpoison, Mutex, RWLock};
+    use std::sync::Mutex as StdMutex;
+    use std::vec::Vec;
+
+    static FOUND: StdMutex<Vec<(LockClass, LockClass)>> = StdMutex::new(Vec::new());
+
+    fn record(inversion: &Inversion) {
+        FOUND
+            .lock()
+            .unwrap()
+            .push((inversion.acquiring, inversion.held));
+    }
+
+    #[test]
+    fn test_inversion() {
+        lockdep::set_inversion_handler(Some(record));
+        let a = Mutex::new(1);
+        let b = RWLock::new(2);
+        let found = |a: &Mutex<i32>, b: &RWLock<i32>| {
+            FOUND
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|&&x| x == (a.lock_class(), b.lock_class()))
+                .count()
+        };
+
+        for _ in 0..2 {
+            let _a = unpoison(a.lock());
+            let _b = unpoison(b.write());
+        }
+        assert_eq!(found(&a, &b), 0);
+
+        //反过来的顺序只报告一次
+        for _ in 0..2 {
+            let _b = unpoison(b.read());
+            let _a = unpoison(a.lock());
+        }
+        assert_eq!(found(&a, &b), 1);
+
+        //try_lock不会死锁，不做检查
+        let _b = unpoison(b.write());
+        assert!(a.try_lock().is_some());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_moved_guard() {
+        lockdep::set_inversion_handler(Some(record));
+        let a = std::sync::Arc::new(Mutex::new(1));
+        let b = Mutex::new(2);
+        //守卫在别的线程释放之后，当前线程不再持有a
+        let guard = unpoison(a.lock_arc());
+        std::thread::spawn(move || drop(guard)).join().expect("err");
+        drop(unpoison(b.lock()));
+        drop((unpoison(b.lock()), unpoison(a.lock())));
+        assert!(!FOUND
+            .lock()
+            .unwrap()
+            .contains(&(a.lock_class(), b.lock_class())));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_moved_read_guard() {
+        lockdep::set_inversion_handler(Some(record));
+        let a = Mutex::new(1);
+        let b = std::sync::Arc::new(RWLock::new(2));
+        //两个线程都持有b的读锁，另一个线程的守卫在当前线程drop，
+        //只能移除那个线程的记录，当前线程仍然持有b
+        let read = unpoison(b.read_arc());
+        let b2 = b.clone();
+        let moved = std::thread::spawn(move || unpoison(b2.read_arc()))
+            .join()
+            .expect("err");
+        drop(moved);
+        drop(unpoison(a.lock()));
+        drop(read);
+        drop((unpoison(a.lock()), unpoison(b.read())));
+        assert!(FOUND
+            .lock()
+            .unwrap()
+            .contains(&(b.lock_class(), a.lock_class())));
+    }
+}
@@ -0,0 +1,343 @@
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use crate::relax::{Relax, Spin};
+
+///
+/// MCS队列锁
+/// 每个等待的线程把自己的节点挂到队尾，然后只在自己的节点上自旋，
+/// 解锁时由持有者把锁直接交给下一个节点，
+/// 多核下不同的等待者不会争抢同一条cache line
+///
+/// 节点登记在队列里时必须一直存活，守卫被`mem::forget`之后节点的借用就结束了，
+/// 所以安全的接口是[`McsMutex::lock_with`]，节点放在它自己的栈帧上；
+/// 自己提供节点的[`McsMutex::lock`]和[`McsMutex::try_lock`]是`unsafe`的
+/// # Exapmle
+///
+/// ```
+/// use xx_mutex_lock::mcs_mutex::McsMutex;
+///
+/// let locked = McsMutex::new(1);
+/// let value = locked.lock_with(|data| {
+///     *data += 1;
+///     *data
+/// });
+/// assert_eq!(value, 2)
+/// ```
+/// 闭包返回时自动解锁
+pub struct McsMutex<T: ?Sized, R = Spin> {
+    /// 队尾的节点，为空表示没有人持有锁
+    pub(crate) tail: AtomicPtr<McsNode>,
+    _relax: PhantomData<R>,
+    data: UnsafeCell<T>,
+}
+
+/// MCS队列中的节点
+/// 一般直接分配在栈上，在解锁之前不能被移动、复用或者释放，
+/// 使用[`McsMutex::lock`]时需要调用者保证这一点
+#[derive(Debug)]
+pub struct McsNode {
+    next: AtomicPtr<McsNode>,
+    locked: AtomicBool,
+}
+
+/// MCS互斥锁守卫
+/// 当guard被drop时，自动解锁并把锁交给队列中的下一个节点
+pub struct McsMutexGuard<'a, T: ?Sized + 'a> {
+    tail: &'a AtomicPtr<McsNode>,
+    node: &'a McsNode,
+    data: *mut T,
+}
+
+unsafe impl<T: ?Sized + Send, R> Sync for McsMutex<T, R> {}
+unsafe impl<T: ?Sized + Send, R> Send for McsMutex<T, R> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for McsMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Send> Send for McsMutexGuard<'_, T> {}
+
+impl McsNode {
+    pub const fn new() -> Self {
+        McsNode {
+            next: AtomicPtr::new(ptr::null_mut()),
+            locked: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Default for McsNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> McsMutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self::with_relax(data)
+    }
+}
+
+impl<T, R> McsMutex<T, R> {
+    /// 使用指定的等待策略创建MCS锁
+    pub const fn with_relax(data: T) -> Self {
+        McsMutex {
+            tail: AtomicPtr::new(ptr::null_mut()),
+            _relax: PhantomData,
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized, R> McsMutex<T, R> {
+    /// 锁是否被持有(或有线程在排队)，只用于调试，返回值可能立即过时
+    pub fn is_locked(&self) -> bool {
+        !self.tail.load(Ordering::Relaxed).is_null()
+    }
+
+    /// 非阻塞地上锁，只有在队列为空时才会成功，成功时在持有锁期间调用`f`
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::mcs_mutex::McsMutex;
+    ///
+    /// let locked = McsMutex::new(1);
+    /// locked.lock_with(|_| {
+    ///     assert!(locked.try_lock_with(|_| ()).is_none());
+    /// });
+    /// assert_eq!(locked.try_lock_with(|data| *data), Some(1));
+    /// ```
+    #[inline]
+    pub fn try_lock_with<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+        let mut node = McsNode::new();
+        //守卫在这个栈帧里被drop，节点一定比它在队列里的时间活得久
+        let mut guard = unsafe { self.try_lock(&mut node) }?;
+        Some(f(&mut guard))
+    }
+
+    /// 非阻塞地上锁，只有在队列为空时才会成功
+    ///
+    /// # Safety
+    /// 守卫必须被drop，不能被`mem::forget`等方式泄漏，
+    /// 否则节点的借用结束后仍然登记在队列里，释放或复用节点会导致悬垂指针。
+    /// 能使用[`McsMutex::try_lock_with`]时优先使用它
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::mcs_mutex::{McsMutex, McsNode};
+    ///
+    /// let locked = McsMutex::new(1);
+    /// let mut node1 = McsNode::new();
+    /// let mut node2 = McsNode::new();
+    /// let lock_guard = unsafe { locked.try_lock(&mut node1) };
+    /// assert!(lock_guard.is_some());
+    /// assert!(unsafe { locked.try_lock(&mut node2) }.is_none());
+    /// ```
+    /// 不写`unsafe`时无法通过编译
+    /// ```compile_fail
+    /// use xx_mutex_lock::mcs_mutex::{McsMutex, McsNode};
+    ///
+    /// let locked = McsMutex::new(1);
+    /// let mut node = McsNode::new();
+    /// core::mem::forget(locked.try_lock(&mut node));
+    /// ```
+    #[inline]
+    pub unsafe fn try_lock<'a>(&'a self, node: &'a mut McsNode) -> Option<McsMutexGuard<'a, T>> {
+        let node: &'a McsNode = node;
+        node.next.store(ptr::null_mut(), Ordering::Relaxed);
+        if self
+            .tail
+            .compare_exchange(
+                ptr::null_mut(),
+                node as *const _ as *mut _,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+        {
+            Some(self.guard(node))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn guard<'a>(&'a self, node: &'a McsNode) -> McsMutexGuard<'a, T> {
+        McsMutexGuard {
+            tail: &self.tail,
+            node,
+            data: self.data.get(),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized, R: Relax> McsMutex<T, R> {
+    /// 上锁并在持有锁期间调用`f`，节点放在这个函数自己的栈帧上
+    ///# Examle
+    /// ```
+    /// use xx_mutex_lock::mcs_mutex::McsMutex;
+    ///
+    /// let locked = McsMutex::new(1);
+    /// locked.lock_with(|data| *data += 1);
+    /// assert_eq!(locked.lock_with(|data| *data), 2)
+    /// ```
+    pub fn lock_with<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        let mut node = McsNode::new();
+        //守卫在这个栈帧里被drop，节点一定比它在队列里的时间活得久
+        let mut guard = unsafe { self.lock(&mut node) };
+        f(&mut guard)
+    }
+
+    /// 上锁，节点在守卫存在期间一直被借用
+    ///
+    /// # Safety
+    /// 守卫必须被drop，不能被`mem::forget`等方式泄漏，
+    /// 否则节点的借用结束后仍然登记在队列里，
+    /// 下一次上锁会通过指向已释放节点的指针写入。
+    /// 能使用[`McsMutex::lock_with`]时优先使用它
+    ///# Examle
+    /// ```
+    /// use xx_mutex_lock::mcs_mutex::{McsMutex, McsNode};
+    ///
+    /// let locked = McsMutex::new(1);
+    /// let mut node = McsNode::new();
+    /// let mut lock_guard = unsafe { locked.lock(&mut node) };
+    /// *lock_guard += 1;
+    /// assert_eq!(*lock_guard, 2)
+    /// ```
+    /// 泄漏守卫后释放节点再上锁，在安全代码里无法通过编译
+    /// ```compile_fail
+    /// use xx_mutex_lock::mcs_mutex::{McsMutex, McsNode};
+    ///
+    /// let locked = McsMutex::new(1);
+    /// {
+    ///     let mut node = McsNode::new();
+    ///     core::mem::forget(locked.lock(&mut node));
+    /// }
+    /// let mut node = McsNode::new();
+    /// let _guard = locked.lock(&mut node);
+    /// ```
+    pub unsafe fn lock<'a>(&'a self, node: &'a mut McsNode) -> McsMutexGuard<'a, T> {
+        let node: &'a McsNode = node;
+        let node_ptr = node as *const _ as *mut McsNode;
+        node.next.store(ptr::null_mut(), Ordering::Relaxed);
+        node.locked.store(true, Ordering::Relaxed);
+
+        //把自己挂到队尾，拿到前一个节点
+        let prev = self.tail.swap(node_ptr, Ordering::AcqRel);
+        if !prev.is_null() {
+            //前一个节点在解锁前会等待next被设置，所以这时它一定还活着
+            unsafe { (*prev).next.store(node_ptr, Ordering::Release) };
+            let mut relax = R::default();
+            while node.locked.load(Ordering::Acquire) {
+                relax.relax();
+            }
+        }
+        self.guard(node)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for McsMutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for McsMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for McsMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        let node_ptr = self.node as *const _ as *mut McsNode;
+        let mut next = self.node.next.load(Ordering::Acquire);
+        if next.is_null() {
+            //没有后继，直接把队列清空
+            if self
+                .tail
+                .compare_exchange(
+                    node_ptr,
+                    ptr::null_mut(),
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                return;
+            }
+            //有线程已经挂到了队尾，但还没来得及设置next，这段时间很短
+            loop {
+                next = self.node.next.load(Ordering::Acquire);
+                if !next.is_null() {
+                    break;
+                }
+                core::hint::spin_loop();
+            }
+        }
+        //把锁交给下一个节点
+        unsafe { (*next).locked.store(false, Ordering::Release) };
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::mcs_mutex::{McsMutex, McsNode};
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn test() {
+        let lock = Arc::new(McsMutex::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        lock.lock_with(|data| *data += 1);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(lock.lock_with(|data| *data), 400);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn test_try_lock() {
+        let lock = McsMutex::new(1);
+        let mut node1 = McsNode::new();
+        let mut node2 = McsNode::new();
+        let guard = unsafe { lock.try_lock(&mut node1) };
+        assert!(guard.is_some());
+        assert!(unsafe { lock.try_lock(&mut node2) }.is_none());
+        assert!(lock.try_lock_with(|_| ()).is_none());
+        drop(guard);
+        assert!(unsafe { lock.try_lock(&mut node2) }.is_some());
+        assert_eq!(lock.try_lock_with(|data| *data), Some(1));
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn test_lock_with_panic() {
+        //闭包panic时守卫照常被drop，锁会被释放
+        let lock = McsMutex::new(1);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            lock.lock_with(|_| panic!("panic in lock_with"));
+        }));
+        assert!(res.is_err());
+        assert!(!lock.is_locked());
+        assert_eq!(lock.lock_with(|data| *data), 1);
+    }
+}
@@ -1,109 +1,843 @@
 use core::{
-    cell::UnsafeCell,
+    marker::PhantomData,
+    num::NonZeroUsize,
     ops::{Deref, DerefMut},
-    sync::atomic::{AtomicBool, Ordering},
+    sync::atomic::Ordering,
 };
+
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+#[cfg(feature = "deadlock_detection")]
+use crate::deadlock;
+#[cfg(feature = "lockdep")]
+use crate::lockdep::{self, LockClass};
+#[cfg(debug_assertions)]
+use crate::owner::OwnerCell;
+#[cfg(feature = "std")]
+use crate::poison::{self, LockResult};
+#[cfg(feature = "registry")]
+use crate::registry::{self, Source};
+use crate::relax::{Relax, Spin};
+#[cfg(feature = "stats")]
+use crate::stats::{Histograms, Hold, LockStats, Stats};
+use crate::sync::{Access, AtomicBool, UnsafeCell};
+#[cfg(feature = "tracing")]
+use crate::trace;
+#[cfg(feature = "watchdog")]
+use crate::watchdog::{Report, Watchdog};
 ///
 /// 互斥锁(自旋锁实现的互斥锁)
 /// 当线程未持有锁时会一直循环，直到持有锁了
 /// # Exapmle
 ///
 /// ```
+/// use xx_mutex_lock::Mutex;
+///
 /// let locked = Mutex::new(1);
-/// let mut lock_guard = locked.lock()
+/// # #[cfg(feature = "std")]
+/// # let mut lock_guard = locked.lock().unwrap();
+/// # #[cfg(not(feature = "std"))]
+/// let mut lock_guard = locked.lock();
 /// *lock_guard += 1;
 /// assert_eq!(*lock_guard, 2)
 /// ```
 /// 当guard被drop时，自动解锁
-pub struct Mutex<T: ?Sized> {
-    pub(crate) lock: AtomicBool,
+///
+/// 等待锁时的行为由类型参数`R`决定，默认为[`Spin`]，参见[`crate::relax`]
+///
+/// 在`std` feature下，持有锁的线程panic会让锁中毒，
+/// 之后的`lock`返回[`LockResult`]，参见[`crate::poison`]
+///
+/// `T`可以是切片或trait对象，`&Mutex<[u8; N]>`、`Box<Mutex<Foo>>`
+/// 可以直接转换成`&Mutex<[u8]>`、`Box<Mutex<dyn Trait>>`
+/// ```
+/// use xx_mutex_lock::Mutex;
+///
+/// let locked = Mutex::new([1u8, 2, 3]);
+/// let slice: &Mutex<[u8]> = &locked;
+/// slice.try_lock().unwrap()[0] = 4;
+/// assert_eq!(*locked.try_lock().unwrap(), [4, 2, 3]);
+/// ```
+///
+/// 在`lockdep` feature下会检查加锁顺序，参见[`crate::lockdep`]
+///
+/// 调试构建下会记录持有者，同一个上下文重复`lock`时panic，参见[`crate::owner`]。
+/// 和标准库一样，[`MutexGuard`]不实现`Send`，可以移动的`ArcMutexGuard`不记录持有者
+/// ```compile_fail
+/// use xx_mutex_lock::Mutex;
+///
+/// let locked = Mutex::new(1);
+/// let lock_guard = locked.try_lock().unwrap();
+/// std::thread::scope(|s| {
+///     s.spawn(move || drop(lock_guard));
+/// });
+/// ```
+///
+/// 在`stats` feature下会统计获取次数和等待、持有时间，参见[`crate::stats`]
+///
+/// 在`tracing` feature下会为竞争和长时间等待发出事件，参见[`crate::trace`]
+pub struct Mutex<T: ?Sized, R = Spin> {
+    pub(crate) raw: RawSpin<R>,
+    #[cfg(feature = "std")]
+    poison: poison::Flag,
+    #[cfg(debug_assertions)]
+    owner: OwnerCell,
+    #[cfg(feature = "lockdep")]
+    class: LockClass,
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    name: Option<&'static str>,
+    #[cfg(feature = "stats")]
+    stats: LockStats,
     data: UnsafeCell<T>,
 }
 
+/// 不保护任何数据的自旋锁，[`Mutex`]的底层实现
+/// 需要手动调用`lock`和`unlock`，
+/// 在`lock_api` feature下实现了`lock_api::RawMutex`，参见[`crate::lock_api`]
+pub struct RawSpin<R = Spin> {
+    pub(crate) lock: AtomicBool,
+    _relax: PhantomData<R>,
+}
+
 /// 互斥锁守卫(自旋锁实现的互斥锁)
 /// 当守卫存在时，表示上锁，
 /// 首位持有期间的代码是临界代码
 /// # Exapmle
 ///
 /// ```
+/// use xx_mutex_lock::Mutex;
+///
 /// let locked = Mutex::new(1);
-/// let lock_guard = locked.lock()
+/// # #[cfg(feature = "std")]
+/// # let lock_guard = locked.lock().unwrap();
+/// # #[cfg(not(feature = "std"))]
+/// let lock_guard = locked.lock();
 /// assert_eq!(*lock_guard, 1)
 /// ```
 /// 当guard被drop时，自动解锁
-pub struct MutexGuard<'a, T: ?Sized + 'a> {
+///
+/// 类型参数`R`和锁的等待策略相同，[`Condvar`](crate::Condvar)等待之后用它重新上锁
+pub struct MutexGuard<'a, T: ?Sized + 'a, R = Spin> {
+    mutex: &'a Mutex<T, R>,
+    #[cfg(feature = "std")]
+    panicking: poison::Guard,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    hold: Hold<'a>,
+    data: *mut T,
+    access: Access,
+}
+
+/// 映射后的互斥锁守卫，由[`MutexGuard::map`]得到
+/// 只能访问原来数据的一部分，drop时仍然解开原来的锁
+pub struct MappedMutexGuard<'a, T: ?Sized + 'a> {
     lock: &'a AtomicBool,
+    #[cfg(feature = "std")]
+    poison: &'a poison::Flag,
+    #[cfg(feature = "std")]
+    panicking: poison::Guard,
+    #[cfg(debug_assertions)]
+    owner: &'a OwnerCell,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    hold: Hold<'a>,
+    data: *mut T,
+    access: Access,
+}
+
+/// 持有锁的Arc的互斥锁守卫，由[`Mutex::lock_arc`]得到，只在`alloc` feature下可用
+/// 不借用锁，可以移动到其他线程或保存在结构体中，守卫存在期间锁不会被释放
+#[cfg(feature = "alloc")]
+pub struct ArcMutexGuard<T: ?Sized, R = Spin> {
+    lock: Arc<Mutex<T, R>>,
+    #[cfg(feature = "std")]
+    panicking: poison::Guard,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    since: Option<u64>,
     data: *mut T,
+    access: Access,
 }
 
-unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
-unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send, R> Sync for Mutex<T, R> {}
+unsafe impl<T: ?Sized + Send, R> Send for Mutex<T, R> {}
 
-unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
-unsafe impl<T: ?Sized + Send> Send for MutexGuard<'_, T> {}
+//守卫对应的持有者和lockdep、死锁检测的记录都属于拿到锁的线程，
+//守卫在任何构建下都不能移动到其他线程，需要移动时使用`lock_arc`
+unsafe impl<T: ?Sized + Sync, R> Sync for MutexGuard<'_, T, R> {}
 
-impl<T> Mutex<T> {
-    pub const fn new(data: T) -> Self {
-        Mutex {
-            lock: AtomicBool::new(false),
-            data: UnsafeCell::new(data),
+unsafe impl<T: ?Sized + Sync> Sync for MappedMutexGuard<'_, T> {}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Sync, R> Sync for ArcMutexGuard<T, R> {}
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Send, R> Send for ArcMutexGuard<T, R> {}
+
+impl RawSpin {
+    loom_const_fn! {
+        pub const fn new() -> Self {
+            Self::with_relax()
         }
     }
+}
 
-    fn is_locked(&self) -> bool {
+impl Default for RawSpin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> RawSpin<R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建自旋锁
+        pub const fn with_relax() -> Self {
+            RawSpin {
+                lock: AtomicBool::new(false),
+                _relax: PhantomData,
+            }
+        }
+    }
+
+    /// 锁是否被持有，只用于调试，返回值可能立即过时
+    #[inline]
+    pub fn is_locked(&self) -> bool {
         self.lock.load(Ordering::Relaxed)
     }
-    /// 上锁
-    ///# Examle
-    /// ```
-    /// let locked = Mutex::new(1);
-    /// let mut lock_guard = locked.lock()
-    /// *lock_guard += 1;
-    /// assert_eq!(*lock_guard, 2)
-    /// ```
-    pub fn lock(&self) -> MutexGuard<T> {
+
+    /// 非阻塞地上锁，成功时返回true
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.lock
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// 解锁
+    ///
+    /// # Safety
+    /// 只能由持有锁的一方调用
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        self.lock.store(false, Ordering::Release)
+    }
+}
+
+impl<R: Relax> RawSpin<R> {
+    /// 上锁，锁被持有时按照`R`的策略等待
+    #[inline]
+    pub fn lock(&self) {
+        self.lock_with(|| ())
+    }
+
+    /// 上锁，每次等待后调用一次`on_relax`
+    #[inline]
+    pub(crate) fn lock_with(&self, mut on_relax: impl FnMut()) {
         while self
             .lock
             .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
             .is_err()
         {
+            let mut relax = R::default();
             while self.is_locked() {
-                core::hint::spin_loop();
+                relax.relax();
+                on_relax();
             }
         }
+    }
+}
+
+impl<T> Mutex<T> {
+    loom_const_fn! {
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn new(data: T) -> Self {
+            Self::with_relax(data)
+        }
+    }
+
+    loom_const_fn! {
+        /// 创建带名字的互斥锁，名字只用于诊断输出，例如看门狗的报告、`tracing`事件和登记表
+        /// # Example
+        /// ```
+        /// use xx_mutex_lock::Mutex;
+        ///
+        /// static DEVICES: Mutex<u32> = Mutex::named("devices", 0);
+        /// ```
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        #[allow(unused_variables)]
+        pub const fn named(name: &'static str, data: T) -> Self {
+            #[allow(unused_mut)]
+            let mut mutex = Self::with_relax(data);
+            #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+            {
+                mutex.name = Some(name);
+            }
+            mutex
+        }
+    }
+}
+
+impl<T, R> Mutex<T, R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建互斥锁
+        /// # Example
+        /// ```
+        /// use xx_mutex_lock::{Backoff, Mutex};
+        ///
+        /// let locked: Mutex<_, Backoff> = Mutex::with_relax(1);
+        /// assert_eq!(*locked.try_lock_spins(100).unwrap(), 1);
+        /// ```
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn with_relax(data: T) -> Self {
+            Mutex {
+                raw: RawSpin::with_relax(),
+                #[cfg(feature = "std")]
+                poison: poison::Flag::new(),
+                #[cfg(debug_assertions)]
+                owner: OwnerCell::new(),
+                #[cfg(feature = "lockdep")]
+                class: LockClass::caller(),
+                #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+                name: None,
+                #[cfg(feature = "stats")]
+                stats: LockStats::new(),
+                data: UnsafeCell::new(data),
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, R> Mutex<T, R> {
+    fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// 非阻塞地上锁，锁已被持有时立即返回None
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::Mutex;
+    ///
+    /// let locked = Mutex::new(1);
+    /// let lock_guard = locked.try_lock();
+    /// assert!(lock_guard.is_some());
+    /// assert!(locked.try_lock().is_none());
+    /// ```
+    #[inline]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T, R>> {
+        if self.raw.try_lock() {
+            #[cfg(feature = "stats")]
+            self.stats.acquired(0, None);
+            Some(self.guard())
+        } else {
+            None
+        }
+    }
+
+    #[cfg(any(feature = "watchdog", feature = "tracing"))]
+    #[inline]
+    fn addr(&self) -> *const () {
+        self as *const Self as *const ()
+    }
+
+    #[inline]
+    fn guard(&self) -> MutexGuard<'_, T, R> {
+        #[cfg(feature = "lockdep")]
+        let entry = lockdep::acquired(&self.raw.lock, self.class);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::acquired(&self.raw.lock);
+        #[cfg(debug_assertions)]
+        self.owner.set();
+        let (data, access) = self.data.get();
         MutexGuard {
-            lock: &self.lock,
-            data: self.data.get(),
+            mutex: self,
+            #[cfg(all(feature = "std", not(feature = "tracing")))]
+            panicking: self.poison.guard(),
+            #[cfg(all(feature = "std", feature = "tracing"))]
+            panicking: self.poison.guard(trace::LockId {
+                kind: "Mutex",
+                name: self.name,
+                addr: self.addr(),
+            }),
+            #[cfg(feature = "lockdep")]
+            lockdep: entry,
+            #[cfg(feature = "stats")]
+            hold: self.stats.hold(),
+            data,
+            access,
         }
     }
 
     pub fn get_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.data.get() }
+        self.data.get_mut()
+    }
+
+    /// 锁是否中毒，即是否有线程在持有锁时panic
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// 清除中毒标记，调用者需要保证数据已经恢复到一致的状态
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.poison.clear()
+    }
+
+    /// 持有锁的上下文的标识，只在调试构建下记录，参见[`crate::owner`]
+    #[cfg(debug_assertions)]
+    pub fn owner(&self) -> Option<NonZeroUsize> {
+        self.owner.get()
+    }
+
+    /// 持有锁的上下文的标识，只在调试构建下记录，这里总是返回None
+    #[cfg(not(debug_assertions))]
+    pub fn owner(&self) -> Option<NonZeroUsize> {
+        None
+    }
+
+    /// 由[`Mutex::named`]设置的名字
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// 锁的统计快照，参见[`crate::stats`]
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// 清零统计信息
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset()
+    }
+
+    /// 挂上记录等待时间和持有时间的直方图，参见[`crate::stats::Histograms`]
+    #[cfg(feature = "stats")]
+    pub fn set_histograms(&self, histograms: &'static Histograms) {
+        self.stats.set_histograms(histograms)
+    }
+
+    /// 登记到全局登记表，登记不会撤销，所以锁必须是`'static`的，参见[`crate::registry`]
+    #[cfg(feature = "registry")]
+    pub fn register(&'static self) {
+        registry::register("Mutex", self.name, self, Source::Mutex(&self.raw.lock))
+    }
+
+    /// 锁所属的锁类，默认是创建锁的位置
+    #[cfg(feature = "lockdep")]
+    pub fn lock_class(&self) -> LockClass {
+        self.class
+    }
+
+    /// 修改锁所属的锁类
+    #[cfg(feature = "lockdep")]
+    pub fn set_lock_class(&mut self, class: LockClass) {
+        self.class = class;
+    }
+}
+
+impl<T: ?Sized, R: Relax> Mutex<T, R> {
+    /// 上锁
+    ///# Examle
+    /// ```
+    /// use xx_mutex_lock::Mutex;
+    ///
+    /// let locked = Mutex::new(1);
+    /// let mut lock_guard = locked.lock();
+    /// *lock_guard += 1;
+    /// assert_eq!(*lock_guard, 2)
+    /// ```
+    #[cfg(not(feature = "std"))]
+    pub fn lock(&self) -> MutexGuard<'_, T, R> {
+        self.acquire()
+    }
+
+    /// 上锁，锁中毒时返回[`PoisonError`](crate::poison::PoisonError)，
+    /// 里面仍然带着守卫
+    ///# Examle
+    /// ```
+    /// use xx_mutex_lock::Mutex;
+    ///
+    /// let locked = Mutex::new(1);
+    /// let mut lock_guard = locked.lock().unwrap();
+    /// *lock_guard += 1;
+    /// assert_eq!(*lock_guard, 2)
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T, R>> {
+        let guard = self.acquire();
+        poison::map_result(self.poison.get(), guard)
+    }
+
+    #[inline]
+    fn acquire(&self) -> MutexGuard<'_, T, R> {
+        #[cfg(debug_assertions)]
+        self.owner.check("Mutex", self);
+        #[cfg(feature = "lockdep")]
+        lockdep::check(&self.raw.lock, self.class);
+        #[cfg(feature = "deadlock_detection")]
+        let waiting = deadlock::wait(&self.raw.lock);
+        #[cfg(feature = "stats")]
+        let start = self.stats.start();
+        #[cfg(any(feature = "stats", feature = "tracing"))]
+        let mut spins = 0;
+        #[cfg(feature = "watchdog")]
+        let mut watchdog = Watchdog::new();
+        self.raw.lock_with(|| {
+            #[cfg(any(feature = "stats", feature = "tracing"))]
+            {
+                spins += 1;
+            }
+            #[cfg(feature = "tracing")]
+            trace::waiting("Mutex", self.name, self.addr(), spins);
+            #[cfg(feature = "watchdog")]
+            watchdog.tick(|spins| self.report(spins));
+        });
+        #[cfg(feature = "deadlock_detection")]
+        drop(waiting);
+        #[cfg(feature = "stats")]
+        self.stats.acquired(spins, start);
+        #[cfg(feature = "tracing")]
+        if spins != 0 {
+            trace::contended("Mutex", self.name, self.addr(), spins);
+        }
+        self.guard()
+    }
+
+    #[cfg(feature = "watchdog")]
+    #[cold]
+    fn report(&self, spins: u64) -> Report {
+        Report {
+            lock: self.addr() as usize,
+            kind: "Mutex",
+            name: self.name,
+            state: self.raw.lock.load(Ordering::Relaxed) as isize,
+            owner: self.owner(),
+            spins,
+        }
+    }
+
+    /// 最多等待spins次尝试上锁，超过次数仍未拿到锁时返回None
+    /// 每次等待调用一次`R::relax`
+    /// 用于中断处理、看门狗等不能无限等待的路径
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::Mutex;
+    ///
+    /// let locked = Mutex::new(1);
+    /// let lock_guard = locked.try_lock();
+    /// assert!(locked.try_lock_spins(100).is_none());
+    /// drop(lock_guard);
+    /// assert!(locked.try_lock_spins(100).is_some());
+    /// ```
+    pub fn try_lock_spins(&self, spins: usize) -> Option<MutexGuard<'_, T, R>> {
+        let mut spins = spins;
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return Some(guard);
+            }
+            //每一轮至少消耗一次等待，保证总的等待次数有上限
+            let mut relax = R::default();
+            loop {
+                if spins == 0 {
+                    return None;
+                }
+                spins -= 1;
+                relax.relax();
+                if !self.is_locked() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized, R> Deref for MutexGuard<'a, T, R> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized, R> DerefMut for MutexGuard<'a, T, R> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized, R> Drop for MutexGuard<'a, T, R> {
+    fn drop(&mut self) {
+        let mutex = self.mutex;
+        #[cfg(feature = "std")]
+        mutex.poison.done(&self.panicking);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(&mutex.raw.lock);
+        #[cfg(debug_assertions)]
+        mutex.owner.clear();
+        #[cfg(feature = "stats")]
+        self.hold.release();
+        self.access.end();
+        unsafe { mutex.raw.unlock() }
+    }
+}
+
+impl<'a, T: ?Sized, R> MutexGuard<'a, T, R> {
+    /// 把守卫映射到被保护数据的一部分上，锁在返回的守卫drop时才解开
+    /// 为了不和`T`的方法冲突，需要写成`MutexGuard::map(guard, ...)`
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::{Mutex, MutexGuard};
+    ///
+    /// let locked = Mutex::new((1, 2));
+    /// let lock_guard = locked.try_lock().unwrap();
+    /// let mut second = MutexGuard::map(lock_guard, |data| &mut data.1);
+    /// *second += 1;
+    /// assert!(locked.try_lock().is_none());
+    /// drop(second);
+    /// assert_eq!(*locked.try_lock().unwrap(), (1, 3));
+    /// ```
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedMutexGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let data = f(unsafe { &mut *orig.data }) as *mut U;
+        Self::into_mapped(orig, data)
+    }
+
+    /// 和[`MutexGuard::map`]一样，但闭包返回None时把原来的守卫还回去
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedMutexGuard<'a, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        match f(unsafe { &mut *orig.data }) {
+            Some(data) => {
+                let data = data as *mut U;
+                Ok(Self::into_mapped(orig, data))
+            }
+            None => Err(orig),
+        }
+    }
+
+    #[inline]
+    fn into_mapped<U: ?Sized>(mut orig: Self, data: *mut U) -> MappedMutexGuard<'a, U> {
+        let mapped = MappedMutexGuard {
+            lock: &orig.mutex.raw.lock,
+            #[cfg(feature = "std")]
+            poison: &orig.mutex.poison,
+            #[cfg(feature = "std")]
+            panicking: orig.panicking,
+            #[cfg(debug_assertions)]
+            owner: &orig.mutex.owner,
+            #[cfg(feature = "lockdep")]
+            lockdep: orig.lockdep,
+            #[cfg(feature = "stats")]
+            hold: orig.hold,
+            data,
+            access: core::mem::take(&mut orig.access),
+        };
+        //锁的所有权转移到了新的守卫上，原来的守卫不能再解锁
+        core::mem::forget(orig);
+        mapped
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn is_poisoned(guard: &Self) -> bool {
+        guard.mutex.poison.get()
+    }
+}
+
+impl<'a, T: ?Sized, R: Relax> MutexGuard<'a, T, R> {
+    /// 解锁后执行`f`，再走一遍和[`Mutex::lock`]相同的上锁流程，供[`Condvar`](crate::Condvar)使用
+    /// 等待期间各个feature的记录里都不再有这把锁
+    pub(crate) fn unlocked(guard: Self, f: impl FnOnce()) -> Self {
+        let mutex = guard.mutex;
+        drop(guard);
+        f();
+        mutex.acquire()
     }
 }
 
-impl<'a, T> Deref for MutexGuard<'a, T> {
+impl<'a, T: ?Sized> Deref for MappedMutexGuard<'a, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         unsafe { &*self.data }
     }
 }
 
-impl<'a, T> DerefMut for MutexGuard<'a, T> {
+impl<'a, T: ?Sized> DerefMut for MappedMutexGuard<'a, T> {
     fn deref_mut(&mut self) -> &mut T {
         unsafe { &mut *self.data }
     }
 }
 
-impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
+impl<'a, T: ?Sized> Drop for MappedMutexGuard<'a, T> {
     fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        self.poison.done(&self.panicking);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(self.lock);
+        #[cfg(debug_assertions)]
+        self.owner.clear();
+        #[cfg(feature = "stats")]
+        self.hold.release();
+        self.access.end();
         self.lock.store(false, Ordering::Release)
     }
 }
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> Mutex<T, R> {
+    /// 非阻塞地上锁，得到持有锁的Arc的守卫
+    #[inline]
+    pub fn try_lock_arc(self: &Arc<Self>) -> Option<ArcMutexGuard<T, R>> {
+        self.try_lock().map(|guard| self.into_arc_guard(guard))
+    }
+
+    #[inline]
+    fn into_arc_guard(self: &Arc<Self>, mut guard: MutexGuard<'_, T, R>) -> ArcMutexGuard<T, R> {
+        let arc_guard = ArcMutexGuard {
+            lock: self.clone(),
+            #[cfg(feature = "std")]
+            panicking: guard.panicking,
+            #[cfg(feature = "lockdep")]
+            lockdep: guard.lockdep,
+            #[cfg(feature = "stats")]
+            since: guard.hold.since,
+            data: guard.data,
+            access: core::mem::take(&mut guard.access),
+        };
+        //Arc守卫可以移动到其他线程，不再记录持有者
+        #[cfg(debug_assertions)]
+        self.owner.clear();
+        //锁的所有权转移到了新的守卫上，原来的守卫不能再解锁
+        core::mem::forget(guard);
+        arc_guard
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R: Relax> Mutex<T, R> {
+    /// 上锁，得到持有锁的Arc的守卫
+    /// # Example
+    /// ```
+    /// use std::sync::Arc;
+    /// use xx_mutex_lock::Mutex;
+    ///
+    /// let locked = Arc::new(Mutex::new(1));
+    /// # #[cfg(feature = "std")]
+    /// # let mut lock_guard = locked.lock_arc().unwrap();
+    /// # #[cfg(not(feature = "std"))]
+    /// let mut lock_guard = locked.lock_arc();
+    /// std::thread::spawn(move || *lock_guard += 1).join().unwrap();
+    /// assert_eq!(*locked.try_lock().unwrap(), 2);
+    /// ```
+    #[cfg(not(feature = "std"))]
+    pub fn lock_arc(self: &Arc<Self>) -> ArcMutexGuard<T, R> {
+        let guard = self.acquire();
+        self.into_arc_guard(guard)
+    }
+
+    /// 上锁，得到持有锁的Arc的守卫，锁中毒时返回[`PoisonError`](crate::poison::PoisonError)
+    #[cfg(feature = "std")]
+    pub fn lock_arc(self: &Arc<Self>) -> LockResult<ArcMutexGuard<T, R>> {
+        let guard = self.acquire();
+        let guard = self.into_arc_guard(guard);
+        poison::map_result(self.poison.get(), guard)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> ArcMutexGuard<T, R> {
+    /// 守卫对应的锁
+    pub fn mutex(guard: &Self) -> &Arc<Mutex<T, R>> {
+        &guard.lock
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> Deref for ArcMutexGuard<T, R> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> DerefMut for ArcMutexGuard<T, R> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> Drop for ArcMutexGuard<T, R> {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        self.lock.poison.done(&self.panicking);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(&self.lock.raw.lock);
+        #[cfg(feature = "stats")]
+        self.lock.stats.released(self.since);
+        self.access.end();
+        unsafe { self.lock.raw.unlock() }
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawMutex for RawSpin<R> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = RawSpin::with_relax();
+
+    type GuardMarker = ::lock_api::GuardSend;
+
+    #[inline]
+    fn lock(&self) {
+        RawSpin::lock(self)
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        RawSpin::try_lock(self)
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        RawSpin::unlock(self)
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        RawSpin::is_locked(self)
+    }
+}
+
+/// 自旋锁没有等待队列，公平解锁和普通解锁一样
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawMutexFair for RawSpin<R> {
+    #[inline]
+    unsafe fn unlock_fair(&self) {
+        RawSpin::unlock(self)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     extern crate std;
     use crate::mutex::Mutex;
+    use crate::test_util::unpoison;
     #[test]
     fn test() {
         use std::sync::Arc;
@@ -113,20 +847,284 @@ pub mod test {
         let t2_lock = lock.clone();
         let t1 = std::thread::spawn(move || {
             for _ in 0..100 {
-                let mut locked = t1_lock.lock();
+                let mut locked = unpoison(t1_lock.lock());
                 *locked += 1;
             }
         });
 
         let t2 = std::thread::spawn(move || {
             for _ in 0..100 {
-                let mut locked = t2_lock.lock();
+                let mut locked = unpoison(t2_lock.lock());
                 *locked += 1;
             }
         });
         t1.join().expect("err");
         t2.join().expect("err");
-        let c = lock.lock();
+        let c = unpoison(lock.lock());
         assert_eq!(*c, 201)
     }
+
+    #[test]
+    fn test_try_lock() {
+        let lock = Mutex::new(1);
+        let guard = lock.try_lock();
+        assert!(guard.is_some());
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+
+        let mut guard = lock.try_lock().expect("err");
+        *guard += 1;
+        drop(guard);
+        assert_eq!(*unpoison(lock.lock()), 2);
+    }
+
+    #[test]
+    fn test_try_lock_spins() {
+        use std::sync::Arc;
+        let lock = Mutex::new(0);
+        let guard = unpoison(lock.lock());
+        assert!(lock.try_lock_spins(0).is_none());
+        assert!(lock.try_lock_spins(1000).is_none());
+        drop(guard);
+        assert!(lock.try_lock_spins(0).is_some());
+
+        //持有者在另一个线程中释放锁后，等待者能在足够大的预算内拿到锁
+        let lock = Arc::new(lock);
+        let guard = unpoison(lock.lock());
+        let t_lock = lock.clone();
+        let t = std::thread::spawn(move || {
+            let mut locked = t_lock.try_lock_spins(usize::MAX).expect("err");
+            *locked += 1;
+        });
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        drop(guard);
+        t.join().expect("err");
+        assert_eq!(*unpoison(lock.lock()), 1);
+    }
+
+    #[test]
+    fn test_relax() {
+        use crate::relax::Backoff;
+        use std::sync::Arc;
+        let lock: Arc<Mutex<_, Backoff>> = Arc::new(Mutex::with_relax(0));
+        let threads: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *unpoison(lock.lock()) += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*unpoison(lock.lock()), 400);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_poison() {
+        use std::sync::Arc;
+        let lock = Arc::new(Mutex::new(0));
+        let t_lock = lock.clone();
+        let res = std::thread::spawn(move || {
+            let mut locked = t_lock.lock().unwrap();
+            *locked += 1;
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(res.is_err());
+        assert!(lock.is_poisoned());
+
+        //中毒后仍然可以通过into_inner拿到守卫和修改到一半的数据
+        assert!(lock.lock().is_err());
+        let locked = lock.lock().unwrap_or_else(|e| e.into_inner());
+        assert_eq!(*locked, 1);
+        drop(locked);
+        assert!(lock.is_poisoned());
+
+        lock.clear_poison();
+        assert!(!lock.is_poisoned());
+        assert_eq!(*lock.lock().unwrap(), 1);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_recursive_lock() {
+        use crate::owner::OwnerId;
+        use crate::test_util::{track_owner, TestOwner};
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        track_owner();
+        let lock = Mutex::new(0);
+        let guard = unpoison(lock.lock());
+        assert_eq!(lock.owner(), Some(TestOwner::current()));
+
+        //持有锁时再次上锁panic，而不是永远自旋
+        let res = catch_unwind(AssertUnwindSafe(|| drop(lock.lock())));
+        let msg = *res
+            .expect_err("err")
+            .downcast::<std::string::String>()
+            .expect("err");
+        assert!(msg.starts_with("recursive lock: Mutex"));
+
+        drop(guard);
+        assert_eq!(lock.owner(), None);
+        assert!(lock.try_lock().is_some());
+    }
+
+    #[cfg(all(debug_assertions, feature = "alloc"))]
+    #[test]
+    fn test_moved_arc_guard() {
+        use crate::test_util::track_owner;
+        use std::sync::Arc;
+        track_owner();
+        let lock = Arc::new(Mutex::new(0));
+        let mut guard = unpoison(lock.lock_arc());
+        assert_eq!(lock.owner(), None);
+
+        //守卫移动到另一个线程之后，原来的线程上锁时等待而不是panic
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            *guard += 1;
+        });
+        *unpoison(lock.lock()) += 1;
+        handle.join().expect("err");
+        assert_eq!(*unpoison(lock.lock()), 2);
+    }
+
+    #[test]
+    fn test_map() {
+        use crate::mutex::MutexGuard;
+        let lock = Mutex::new((1, [0u8; 4]));
+        let guard = unpoison(lock.lock());
+        let mut bytes = MutexGuard::map(guard, |data| &mut data.1);
+        bytes[2] = 7;
+        assert!(lock.try_lock().is_none());
+        drop(bytes);
+
+        let guard = unpoison(lock.lock());
+        let guard = match MutexGuard::try_map(guard, |data| data.1.get_mut(10)) {
+            Ok(_) => panic!("index out of range"),
+            Err(guard) => guard,
+        };
+        assert!(lock.try_lock().is_none());
+        let Ok(mut first) = MutexGuard::try_map(guard, |data| data.1.first_mut()) else {
+            panic!("err");
+        };
+        *first = 3;
+        drop(first);
+
+        assert_eq!(*unpoison(lock.lock()), (1, [3, 0, 7, 0]));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_lock_arc() {
+        use std::sync::Arc;
+        use std::vec::Vec;
+
+        struct Holder {
+            guard: crate::mutex::ArcMutexGuard<i32>,
+        }
+
+        let lock = Arc::new(Mutex::new(0));
+        let mut holder = Holder {
+            guard: unpoison(lock.lock_arc()),
+        };
+        *holder.guard += 1;
+        assert!(lock.try_lock().is_none());
+        assert!(lock.try_lock_arc().is_none());
+
+        //守卫可以移动到其他线程中释放
+        std::thread::spawn(move || {
+            *holder.guard += 1;
+            drop(holder);
+        })
+        .join()
+        .expect("err");
+        assert_eq!(*unpoison(lock.lock()), 2);
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *unpoison(lock.lock_arc()) += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        let guard = lock.try_lock_arc().expect("err");
+        assert_eq!(*guard, 402);
+        assert_eq!(Arc::strong_count(&lock), 2);
+        drop(guard);
+        assert_eq!(Arc::strong_count(&lock), 1);
+    }
+
+    #[test]
+    fn test_unsized() {
+        use std::boxed::Box;
+        use std::vec::Vec;
+
+        trait Device {
+            fn poke(&mut self) -> usize;
+        }
+        struct Counter(usize);
+        impl Device for Counter {
+            fn poke(&mut self) -> usize {
+                self.0 += 1;
+                self.0
+            }
+        }
+        struct Fixed;
+        impl Device for Fixed {
+            fn poke(&mut self) -> usize {
+                42
+            }
+        }
+
+        let bytes = Mutex::new([0u8; 4]);
+        let slice: &Mutex<[u8]> = &bytes;
+        unpoison(slice.lock())[1] = 1;
+        assert_eq!(unpoison(slice.lock()).len(), 4);
+        assert_eq!(*unpoison(bytes.lock()), [0, 1, 0, 0]);
+
+        let devices: Vec<Box<Mutex<dyn Device>>> = std::vec![
+            Box::new(Mutex::new(Counter(0))),
+            Box::new(Mutex::new(Fixed))
+        ];
+        assert_eq!(unpoison(devices[0].lock()).poke(), 1);
+        assert_eq!(unpoison(devices[0].lock()).poke(), 2);
+        assert_eq!(devices[1].try_lock().expect("err").poke(), 42);
+    }
+}
+
+#[cfg(all(test, loom))]
+pub mod loom_test {
+    extern crate std;
+    use crate::{test_util::unpoison, Mutex};
+    use loom::{sync::Arc, thread};
+    use std::vec::Vec;
+
+    #[test]
+    fn test_mutual_exclusion() {
+        loom::model(|| {
+            let lock = Arc::new(Mutex::new(0));
+            let threads: Vec<_> = (0..2)
+                .map(|_| {
+                    let lock = lock.clone();
+                    thread::spawn(move || *unpoison(lock.lock()) += 1)
+                })
+                .collect();
+            for t in threads {
+                t.join().expect("Err");
+            }
+            assert_eq!(*unpoison(lock.lock()), 2);
+        });
+    }
 }
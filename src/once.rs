@@ -1,4 +1,10 @@
-use core::sync::atomic::{AtomicU8, Ordering};
+use core::{marker::PhantomData, sync::atomic::Ordering};
+
+use crate::owner::OwnerCell;
+use crate::relax::{Relax, Spin};
+use crate::sync::AtomicU8;
+#[cfg(feature = "watchdog")]
+use crate::watchdog::{Report, Watchdog};
 ///一共四种状态
 /// 用来表示once的运行状态
 ///
@@ -11,24 +17,41 @@ pub mod status {
 use status::*;
 
 /// 确保一段代码即使是在多线程的情况下，也只执行一次
+/// 等待其他线程完成时的行为由类型参数`R`决定
+/// 会记录运行初始化的上下文，初始化过程中同一个上下文再次调用时panic
 /// # Example
-/// ```
+/// ```ignore
 /// use crate::once::Once;
 /// let once = Once::new();
-/// once.call_once(|| {
+/// once.call_once(None, || {
 ///     //run some code here
 /// });
 /// ```
-pub(crate) struct Once {
+pub(crate) struct Once<R = Spin> {
     status: AtomicU8,
+    owner: OwnerCell,
+    _relax: PhantomData<R>,
 }
 
-unsafe impl Sync for Once {}
-unsafe impl Send for Once {}
+unsafe impl<R> Sync for Once<R> {}
+unsafe impl<R> Send for Once<R> {}
 impl Once {
-    pub const fn new() -> Self {
-        Self {
-            status: AtomicU8::new(INCOMPLETE),
+    loom_const_fn! {
+        #[allow(dead_code)]
+        pub const fn new() -> Self {
+            Self::with_relax()
+        }
+    }
+}
+
+impl<R> Once<R> {
+    loom_const_fn! {
+        pub const fn with_relax() -> Self {
+            Self {
+                status: AtomicU8::new(INCOMPLETE),
+                owner: OwnerCell::new(),
+                _relax: PhantomData,
+            }
         }
     }
 
@@ -36,27 +59,43 @@ impl Once {
     pub fn is_completed(&self) -> bool {
         self.status.load(Ordering::Acquire) == COMPLETE
     }
+
+    /// 状态变量，取值见[`status`]
+    #[cfg(feature = "registry")]
+    #[inline]
+    pub fn status(&self) -> &AtomicU8 {
+        &self.status
+    }
+
+    /// 是否正在由当前上下文运行
+    #[inline]
+    pub fn is_running_by_current(&self) -> bool {
+        self.status.load(Ordering::Acquire) == RUNNING && self.owner.is_current()
+    }
+}
+
+impl<R: Relax> Once<R> {
     ///
-    /// 运行只运行一次的代码
+    /// 运行只运行一次的代码，`name`是所属的锁的名字，用于watchdog的报告
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// use crate::once::Once;
     /// let once = Once::new();
-    /// once.call_once(|| {
+    /// once.call_once(None, || {
     ///     std::println!("I only run once")
     /// });
     /// ```
     #[inline]
-    pub fn call_once<F: FnOnce()>(&self, f: F) {
+    pub fn call_once<F: FnOnce()>(&self, name: Option<&'static str>, f: F) {
         //如果没有被初始化才调用
         if !self.is_completed() {
-            self.call(f);
+            self.call(name, f);
         }
     }
 
     #[cold]
-    fn call<F: FnOnce()>(&self, f: F) {
+    fn call<F: FnOnce()>(&self, name: Option<&'static str>, f: F) {
         loop {
             // compare_exchange 是原子的交换两个数字，他的返回值是Result
             // 只有一个线程会成功，成功后将status的值设置成RUNNING
@@ -73,11 +112,14 @@ impl Once {
                 }
                 //另一个线程持有了锁，并且panic了
                 Err(status::PANICKED) => panic!("Once paniced"),
-                //另一个线程正在运行
-                Err(status::RUNNING) => match self.poll() {
-                    Ok(_) => return,
-                    Err(_) => continue,
-                },
+                //另一个线程正在运行，检查是不是自己
+                Err(status::RUNNING) => {
+                    self.owner.check("Once", self);
+                    match self.poll(name) {
+                        Ok(_) => return,
+                        Err(_) => continue,
+                    }
+                }
                 //另一个线程完成了
                 Err(status::COMPLETE) => return,
                 //因为其他原因没交换成功（不应该出现这种情况）
@@ -90,6 +132,7 @@ impl Once {
             let finish = Finish {
                 status: &self.status,
             };
+            self.owner.set();
             //运行所要运行的代码
             f();
             //正常结束，forget掉这个finish,不要设置status为panic
@@ -100,11 +143,26 @@ impl Once {
         }
     }
 
-    fn poll(&self) -> Result<(), u8> {
+    #[cfg_attr(not(feature = "watchdog"), allow(unused_variables))]
+    fn poll(&self, name: Option<&'static str>) -> Result<(), u8> {
+        let mut relax = R::default();
+        #[cfg(feature = "watchdog")]
+        let mut watchdog = Watchdog::new();
         loop {
             match self.status.load(Ordering::Acquire) {
                 status::INCOMPLETE => return Err(INCOMPLETE),
-                status::RUNNING => core::hint::spin_loop(),
+                status::RUNNING => {
+                    relax.relax();
+                    #[cfg(feature = "watchdog")]
+                    watchdog.tick(|spins| Report {
+                        lock: self as *const Self as usize,
+                        kind: "Once",
+                        name,
+                        state: RUNNING as isize,
+                        owner: self.owner.get(),
+                        spins,
+                    });
+                }
                 status::COMPLETE => return Ok(()),
                 status::PANICKED => panic!("Once previously poisoned by a panicked"),
                 _ => {
@@ -137,14 +195,71 @@ pub mod test {
         let once_1 = arc_once.clone();
         let once_2 = arc_once.clone();
         let t1 = std::thread::spawn(move || {
-            once_1.call_once(|| println!("hello , I only hello once"));
+            once_1.call_once(None, || println!("hello , I only hello once"));
         });
 
         let t2 = std::thread::spawn(move || {
-            once_2.call_once(|| println!("hello , I only hello once"));
+            once_2.call_once(None, || println!("hello , I only hello once"));
         });
 
         t1.join().expect("Err");
         t2.join().expect("Err");
     }
+
+    #[test]
+    fn test_recursive_call() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        crate::test_util::track_owner();
+        let once = Once::new();
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            once.call_once(None, || once.call_once(None, || ()))
+        }));
+        let msg = *res
+            .expect_err("err")
+            .downcast::<std::string::String>()
+            .expect("err");
+        assert!(msg.starts_with("recursive lock: Once"));
+    }
+}
+
+#[cfg(all(test, loom))]
+pub mod loom_test {
+    extern crate std;
+    use crate::once::Once;
+    use core::sync::atomic::Ordering;
+    use loom::{
+        sync::{atomic::AtomicUsize, Arc},
+        thread,
+    };
+    use std::{
+        panic::{catch_unwind, AssertUnwindSafe},
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_once_panic() {
+        loom::model(|| {
+            let once = Arc::new(Once::new());
+            let calls = Arc::new(AtomicUsize::new(0));
+            let threads: Vec<_> = (0..2)
+                .map(|_| {
+                    let (once, calls) = (once.clone(), calls.clone());
+                    thread::spawn(move || {
+                        //运行初始化的线程panic，其他线程看到PANICKED后也panic，不会再运行一次
+                        let res = catch_unwind(AssertUnwindSafe(|| {
+                            once.call_once(None, || {
+                                calls.fetch_add(1, Ordering::Relaxed);
+                                panic!("init failed");
+                            })
+                        }));
+                        assert!(res.is_err());
+                    })
+                })
+                .collect();
+            for t in threads {
+                t.join().expect("Err");
+            }
+            assert_eq!(calls.load(Ordering::Relaxed), 1);
+        });
+    }
 }
@@ -1,36 +1,113 @@
-use core::{cell::UnsafeCell, marker::PhantomData, mem::MaybeUninit};
+use core::{marker::PhantomData, mem::MaybeUninit};
 
 use super::once::Once;
+#[cfg(feature = "registry")]
+use crate::registry::{self, Source};
+use crate::relax::{Relax, Spin};
+use crate::sync::UnsafeCell;
+#[cfg(feature = "tracing")]
+use crate::trace;
 
 /// 用于初始化全局变量，只能初始化一次，不能改变
 /// # Example
 ///
 /// ```
-/// use crate::once_lock::OnceLock;
+/// use xx_mutex_lock::OnceLock;
 ///
-/// let INIT = OnceLock::new();
+/// let init = OnceLock::new();
 ///
-/// INIT.get_or_init({
+/// init.get_or_init(|| {
 ///     //run init code here
-/// })
+/// });
 /// ```
-pub struct OnceLock<T = ()> {
-    once: Once,
+///
+/// 等待其他线程初始化时的行为由类型参数`R`决定，默认为[`Spin`]
+///
+/// 和标准库一样，初始化函数不能再访问同一个OnceLock，
+/// 能拿到当前上下文的标识时（参见[`crate::owner`]）会panic，而不是永远等待自己
+pub struct OnceLock<T = (), R = Spin> {
+    once: Once<R>,
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    name: Option<&'static str>,
     data: UnsafeCell<MaybeUninit<T>>,
     _marker: PhantomData<T>,
 }
 
-unsafe impl<T: Sync + Send> Sync for OnceLock<T> {}
-unsafe impl<T: Send> Send for OnceLock<T> {}
+unsafe impl<T: Sync + Send, R> Sync for OnceLock<T, R> {}
+unsafe impl<T: Send, R> Send for OnceLock<T, R> {}
 
 impl<T> OnceLock<T> {
-    pub const fn new() -> Self {
-        Self {
-            once: Once::new(),
-            data: UnsafeCell::new(MaybeUninit::uninit()),
-            _marker: PhantomData,
+    loom_const_fn! {
+        pub const fn new() -> Self {
+            Self::with_relax()
+        }
+    }
+
+    loom_const_fn! {
+        /// 创建带名字的OnceLock，名字只用于诊断输出，参见[`Mutex::named`](crate::Mutex::named)
+        /// # Example
+        /// ```
+        /// use xx_mutex_lock::OnceLock;
+        ///
+        /// static CONFIG: OnceLock<u32> = OnceLock::named("config");
+        /// ```
+        #[allow(unused_variables)]
+        pub const fn named(name: &'static str) -> Self {
+            #[allow(unused_mut)]
+            let mut cell = Self::with_relax();
+            #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+            {
+                cell.name = Some(name);
+            }
+            cell
+        }
+    }
+}
+
+impl<T> Default for OnceLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R> OnceLock<T, R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建OnceLock
+        /// # Example
+        /// ```
+        /// use xx_mutex_lock::{Backoff, OnceLock};
+        ///
+        /// let init: OnceLock<_, Backoff> = OnceLock::with_relax();
+        /// assert_eq!(3, *init.get_or_init(|| 3));
+        /// ```
+        pub const fn with_relax() -> Self {
+            Self {
+                once: Once::with_relax(),
+                #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+                name: None,
+                data: UnsafeCell::new(MaybeUninit::uninit()),
+                _marker: PhantomData,
+            }
         }
     }
+
+    /// 由[`OnceLock::named`]设置的名字
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// 登记到全局登记表，登记不会撤销，所以锁必须是`'static`的，参见[`crate::registry`]
+    #[cfg(feature = "registry")]
+    pub fn register(&'static self) {
+        registry::register(
+            "OnceLock",
+            self.name,
+            self,
+            Source::Once(self.once.status()),
+        )
+    }
+
     #[inline]
     fn is_initialized(&self) -> bool {
         self.once.is_completed()
@@ -38,12 +115,12 @@ impl<T> OnceLock<T> {
 
     ///用法
     /// ```
-    ///use crate::once_lock::OnceLock;
-    /// let INIT = OnceLock::new();
+    /// use xx_mutex_lock::OnceLock;
+    /// let init = OnceLock::new();
+    ///
+    /// init.get_or_init(|| 3);
     ///
-    /// INIT.get_or_init(|| 3);
-    ///  
-    /// assert_eq!(3,INIT.get())
+    /// assert_eq!(Some(&3), init.get())
     /// ```
     #[inline]
     pub fn get(&self) -> Option<&T> {
@@ -54,14 +131,66 @@ impl<T> OnceLock<T> {
             None
         }
     }
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.is_initialized() {
+            Some(unsafe { self.get_unchecked_mut() })
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    unsafe fn get_unchecked(&self) -> &T {
+        self.data.with(|data| (*data).assume_init_ref())
+    }
+    #[inline]
+    unsafe fn get_unchecked_mut(&mut self) -> &mut T {
+        self.data.get_mut().assume_init_mut()
+    }
+    #[inline]
+    pub fn as_mut_ptr(&self) -> *mut T {
+        self.data.with_mut(|data| data.cast::<T>())
+    }
+
+    /// 诊断输出里使用的名字，没有开启保存名字的feature时总是None
+    #[inline]
+    fn diag_name(&self) -> Option<&'static str> {
+        #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+        return self.name;
+        #[cfg(not(any(feature = "watchdog", feature = "tracing", feature = "registry")))]
+        None
+    }
+
+    /// 当前上下文正在初始化时panic，`kind`和`cell`是报告给用户的类型和地址
+    #[inline]
+    pub(crate) fn check_reentrant<L: ?Sized>(&self, kind: &str, cell: &L) {
+        if self.once.is_running_by_current() {
+            let ty = core::any::type_name::<T>();
+            let addr = cell as *const L as *const ();
+            match self.diag_name() {
+                Some(name) => panic!(
+                    "reentrant init: {}<{}> `{}` at {:p} is accessed from its own initializer",
+                    kind, ty, name, addr
+                ),
+                None => panic!(
+                    "reentrant init: {}<{}> at {:p} is accessed from its own initializer",
+                    kind, ty, addr
+                ),
+            }
+        }
+    }
+}
+
+impl<T, R: Relax> OnceLock<T, R> {
     ///用法
     /// ```
-    ///use crate::once_lock::OnceLock;
-    /// let INIT = OnceLock::new();
+    /// use xx_mutex_lock::OnceLock;
+    /// let init = OnceLock::new();
+    ///
+    /// assert!(init.set(3).is_ok());
     ///
-    /// INIT.set(|| 3);
-    ///  
-    /// assert_eq!(3,INIT.get())
+    /// assert_eq!(Some(&3), init.get())
     /// ```
     #[inline]
     pub fn set(&self, data: T) -> Result<(), (&T, T)> {
@@ -72,56 +201,67 @@ impl<T> OnceLock<T> {
             Some(value) => Err((res, value)),
         }
     }
-    #[inline]
-    pub fn get_mut(&mut self) -> Option<&mut T> {
-        if self.is_initialized() {
-            Some(unsafe { self.get_unchecked_mut() })
-        } else {
-            None
-        }
-    }
-
     //用于初始化的方法，
     //可以传入一个闭包,具体用法参见上面的例子
     /// ```
-    ///use crate::once_lock::OnceLock;
-    /// let INIT = OnceLock::new();
+    /// use xx_mutex_lock::OnceLock;
+    /// let init = OnceLock::new();
+    ///
+    /// init.get_or_init(|| 3);
     ///
-    /// INIT.get_or_init(|| 3);
-    ///  
-    /// assert_eq!(3,INIT.get())
+    /// assert_eq!(Some(&3), init.get())
     /// ```
     #[inline]
     pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        self.get_or_init_as("OnceLock", f)
+    }
+
+    /// 和[`OnceLock::get_or_init`]一样，`kind`是诊断输出里使用的类型名
+    #[inline]
+    pub(crate) fn get_or_init_as<F: FnOnce() -> T>(&self, kind: &'static str, f: F) -> &T {
         //将闭包转化为 返回Result的闭包（实际上这里以我的实现只可能返回Ok）
-        match self.try_get_or_init(|| Ok::<T, !>(f())) {
+        match self.try_get_or_init(kind, || Ok::<T, !>(f())) {
             Ok(data) => data,
             Err(_) => panic!("never"),
         }
     }
 
     #[inline]
-    fn try_get_or_init<E, F: FnOnce() -> Result<T, E>>(&self, f: F) -> Result<&T, E> {
+    fn try_get_or_init<E, F: FnOnce() -> Result<T, E>>(
+        &self,
+        kind: &'static str,
+        f: F,
+    ) -> Result<&T, E> {
         //如果此时已经有被初始化了，直接返回
         if let Some(value) = self.get() {
             return Ok(value);
         }
         //实际的初始化函数
-        self.initialized(f)?;
+        self.initialized(kind, f)?;
 
         assert!(self.is_initialized());
 
         Ok(unsafe { self.get_unchecked() })
     }
     #[cold]
-    fn initialized<F: FnOnce() -> Result<T, E>, E>(&self, f: F) -> Result<(), E> {
+    fn initialized<F: FnOnce() -> Result<T, E>, E>(
+        &self,
+        kind: &'static str,
+        f: F,
+    ) -> Result<(), E> {
+        self.check_reentrant(kind, self);
+        #[cfg(feature = "tracing")]
+        let f = || {
+            let addr = self as *const Self as *const ();
+            trace::init(kind, self.name, addr, core::any::type_name::<T>(), f)
+        };
         let mut res = Ok(());
         let slot = &self.data;
         //调用Once的方法，保持多线程下也只运行一次 f
-        self.once.call_once(|| match f() {
+        self.once.call_once(self.diag_name(), || match f() {
             Ok(data) => {
                 //如果成功，则将值写入Self的UnsafeCell
-                unsafe { (*slot.get()).write(data) };
+                slot.with_mut(|slot| unsafe { (*slot).write(data) });
             }
             Err(e) => {
                 res = Err(e);
@@ -130,25 +270,12 @@ impl<T> OnceLock<T> {
         });
         res
     }
-
-    #[inline]
-    unsafe fn get_unchecked(&self) -> &T {
-        (*self.data.get()).assume_init_ref()
-    }
-    #[inline]
-    unsafe fn get_unchecked_mut(&mut self) -> &mut T {
-        (*self.data.get()).assume_init_mut()
-    }
-    #[inline]
-    pub fn as_mut_ptr(&self) -> *mut T {
-        self.data.get().cast::<T>()
-    }
 }
 
-unsafe impl<#[may_dangle] T> Drop for OnceLock<T> {
+unsafe impl<#[may_dangle] T, R> Drop for OnceLock<T, R> {
     fn drop(&mut self) {
         if self.is_initialized() {
-            unsafe { (*self.data.get()).assume_init_drop() }
+            unsafe { self.data.get_mut().assume_init_drop() }
         }
     }
 }
@@ -180,4 +307,35 @@ pub mod test {
         //这个值等于先运行的线程的初始化的值
         std::println!("{:?}", c.unwrap())
     }
+
+    #[test]
+    fn test_reentrant_init() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        crate::test_util::track_owner();
+        let cell: OnceLock<i32> = OnceLock::new();
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            cell.get_or_init(|| *cell.get_or_init(|| 1) + 1);
+        }));
+        let msg = *res
+            .expect_err("err")
+            .downcast::<std::string::String>()
+            .expect("err");
+        assert!(msg.starts_with("reentrant init: OnceLock<i32>"));
+    }
+
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    #[test]
+    fn test_reentrant_init_named() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        crate::test_util::track_owner();
+        let cell: OnceLock<i32> = OnceLock::named("config");
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            cell.get_or_init(|| *cell.get_or_init(|| 1) + 1);
+        }));
+        let msg = *res
+            .expect_err("err")
+            .downcast::<std::string::String>()
+            .expect("err");
+        assert!(msg.starts_with("reentrant init: OnceLock<i32> `config` at"));
+    }
 }
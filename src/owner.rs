@@ -0,0 +1,140 @@
+//! 执行上下文的标识
+//!
+//! 需要知道"谁持有锁"的原语（例如[`ReentrantMutex`](crate::ReentrantMutex)）
+//! 通过[`OwnerId`]获取当前执行上下文的标识。
+//! 在用户态可以用线程标识，在内核里可以用CPU号或任务标识
+//!
+//! 调试构建下[`Mutex`](crate::Mutex)、[`RWLock`](crate::RWLock)的写锁和`Once`
+//! 会通过[`set_owner_provider`]设置的全局标识记录持有者，
+//! 同一个上下文重复上锁时panic而不是永远自旋。
+//! [`OnceLock`](crate::OnceLock)和[`LazyLock`](crate::LazyLock)在所有构建下都会记录
+//! 正在初始化的上下文，初始化函数访问自己时panic。
+//! 链接标准库时（`std`、`lockdep`、`deadlock_detection`或`registry` feature）默认使用[`ThreadOwner`]，
+//! 否则没有设置时不做记录
+use core::{
+    num::NonZeroUsize,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+/// 当前执行上下文的标识
+///
+/// 同一时刻不同的上下文必须返回不同的值，同一个上下文多次调用必须返回相同的值
+/// # Example
+/// ```
+/// use core::num::NonZeroUsize;
+/// use xx_mutex_lock::OwnerId;
+///
+/// struct Cpu;
+///
+/// impl OwnerId for Cpu {
+///     fn current() -> NonZeroUsize {
+///         //假设只有一个CPU
+///         let cpu_id = 0;
+///         NonZeroUsize::new(cpu_id + 1).unwrap()
+///     }
+/// }
+/// ```
+pub trait OwnerId {
+    fn current() -> NonZeroUsize;
+}
+
+/// 用线程局部变量的地址作为线程的标识，只在`std` feature下可用
+#[cfg(feature = "_std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ThreadOwner;
+
+#[cfg(feature = "_std")]
+impl OwnerId for ThreadOwner {
+    #[inline]
+    fn current() -> NonZeroUsize {
+        std::thread_local!(static KEY: u8 = const { 0 });
+        KEY.with(|key| NonZeroUsize::new(key as *const u8 as usize).expect("null thread local"))
+    }
+}
+
+/// 全局的标识函数，空指针表示没有设置
+static PROVIDER: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// 设置调试构建下记录锁的持有者时使用的标识
+/// # Example
+/// ```
+/// use core::num::NonZeroUsize;
+/// use xx_mutex_lock::owner::{set_owner_provider, OwnerId};
+///
+/// struct Cpu;
+///
+/// impl OwnerId for Cpu {
+///     fn current() -> NonZeroUsize {
+///         NonZeroUsize::new(1).unwrap()
+///     }
+/// }
+///
+/// set_owner_provider::<Cpu>();
+/// ```
+pub fn set_owner_provider<O: OwnerId>() {
+    let current: fn() -> NonZeroUsize = O::current;
+    PROVIDER.store(current as *mut (), Ordering::Release);
+}
+
+/// 当前上下文的标识，没有可用的标识时返回None
+pub(crate) fn current() -> Option<NonZeroUsize> {
+    let provider = PROVIDER.load(Ordering::Acquire);
+    if provider.is_null() {
+        //loom的线程共用同一个系统线程，不能用线程局部变量区分
+        #[cfg(all(feature = "_std", not(loom)))]
+        return Some(ThreadOwner::current());
+        #[cfg(any(not(feature = "_std"), loom))]
+        return None;
+    }
+    //只会存入set_owner_provider中的函数指针
+    let current: fn() -> NonZeroUsize = unsafe { core::mem::transmute(provider) };
+    Some(current())
+}
+
+/// 记录锁的持有者，0表示没有持有者或者标识不可用
+pub(crate) struct OwnerCell(AtomicUsize);
+
+impl OwnerCell {
+    pub const fn new() -> Self {
+        OwnerCell(AtomicUsize::new(0))
+    }
+
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    #[inline]
+    pub fn get(&self) -> Option<NonZeroUsize> {
+        NonZeroUsize::new(self.0.load(Ordering::Relaxed))
+    }
+
+    /// 拿到锁之后记录当前上下文
+    #[inline]
+    pub fn set(&self) {
+        self.0
+            .store(current().map_or(0, NonZeroUsize::get), Ordering::Relaxed);
+    }
+
+    /// 释放锁之前清除
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    #[inline]
+    pub fn clear(&self) {
+        self.0.store(0, Ordering::Relaxed);
+    }
+
+    /// 持有者是否是当前上下文
+    /// 持有者只会由持有锁的上下文自己写入，所以和自己比较的结果是准确的
+    #[inline]
+    pub fn is_current(&self) -> bool {
+        current().is_some_and(|current| self.0.load(Ordering::Relaxed) == current.get())
+    }
+
+    /// 当前上下文已经持有锁时panic
+    #[inline]
+    pub fn check<L: ?Sized>(&self, kind: &str, lock: &L) {
+        if self.is_current() {
+            panic!(
+                "recursive lock: {} at {:p} is already held by the current owner",
+                kind, lock as *const L as *const ()
+            );
+        }
+    }
+}
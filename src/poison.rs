@@ -0,0 +1,141 @@
+//! 锁中毒，只在`std` feature下可用
+//!
+//! 持有[`Mutex`](crate::Mutex)或[`RWLock`](crate::RWLock)写锁的线程panic时，
+//! 锁会被标记为中毒，之后的`lock`/`read`/`write`返回[`PoisonError`]，
+//! 提醒调用者被保护的数据可能只修改了一半。
+//! 用法和`std::sync`中的同名类型一致
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(feature = "tracing")]
+use crate::trace::LockId;
+
+/// 中毒标记，嵌在锁里面
+pub(crate) struct Flag {
+    failed: AtomicBool,
+}
+
+impl Flag {
+    pub const fn new() -> Self {
+        Flag {
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// 上锁成功后调用，记录上锁时线程是否已经在panic
+    #[cfg(not(feature = "tracing"))]
+    #[inline]
+    pub fn guard(&self) -> Guard {
+        Guard {
+            panicking: std::thread::panicking(),
+        }
+    }
+
+    /// 上锁成功后调用，记录上锁时线程是否已经在panic，`lock`用于中毒事件
+    #[cfg(feature = "tracing")]
+    #[inline]
+    pub fn guard(&self, lock: LockId) -> Guard {
+        Guard {
+            panicking: std::thread::panicking(),
+            lock,
+        }
+    }
+
+    /// 解锁前调用，持有锁期间开始panic的话标记为中毒
+    #[inline]
+    pub fn done(&self, guard: &Guard) {
+        if !guard.panicking && std::thread::panicking() {
+            self.failed.store(true, Ordering::Relaxed);
+            #[cfg(feature = "tracing")]
+            crate::trace::poisoned(guard.lock.kind, guard.lock.name, guard.lock.addr);
+        }
+    }
+
+    #[inline]
+    pub fn get(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn clear(&self) {
+        self.failed.store(false, Ordering::Relaxed)
+    }
+}
+
+/// 记录上锁时线程是否已经在panic，放在守卫里面。`tracing` feature下还记录锁的标识
+#[derive(Clone, Copy)]
+pub(crate) struct Guard {
+    panicking: bool,
+    #[cfg(feature = "tracing")]
+    lock: LockId,
+}
+
+/// 锁中毒错误，仍然可以通过它拿到守卫
+/// # Example
+/// ```
+/// use std::sync::Arc;
+/// use xx_mutex_lock::Mutex;
+///
+/// let lock = Arc::new(Mutex::new(1));
+/// let t_lock = lock.clone();
+/// let _ = std::thread::spawn(move || {
+///     let _guard = t_lock.lock().unwrap();
+///     panic!();
+/// })
+/// .join();
+///
+/// assert!(lock.is_poisoned());
+/// let guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+/// assert_eq!(*guard, 1);
+/// ```
+pub struct PoisonError<T> {
+    guard: T,
+}
+
+/// `lock`/`read`/`write`的返回值，中毒时返回[`PoisonError`]
+pub type LockResult<G> = Result<G, PoisonError<G>>;
+
+impl<T> PoisonError<T> {
+    pub fn new(guard: T) -> PoisonError<T> {
+        PoisonError { guard }
+    }
+
+    /// 忽略中毒，取出里面的守卫
+    pub fn into_inner(self) -> T {
+        self.guard
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.guard
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> fmt::Debug for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "poisoned lock: another task failed inside".fmt(f)
+    }
+}
+
+impl<T> std::error::Error for PoisonError<T> {}
+
+/// 根据中毒标记把守卫包装成[`LockResult`]
+#[inline]
+pub(crate) fn map_result<G>(poisoned: bool, guard: G) -> LockResult<G> {
+    if poisoned {
+        Err(PoisonError::new(guard))
+    } else {
+        Ok(guard)
+    }
+}
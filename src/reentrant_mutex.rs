@@ -0,0 +1,234 @@
+use core::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::owner::OwnerId;
+use crate::relax::{Relax, Spin};
+
+///
+/// 可重入互斥锁
+/// 同一个执行上下文可以多次上锁而不会死锁，记录重入的次数，
+/// 所有守卫都drop之后才真正解锁。
+/// 因为同一个上下文可能同时持有多个守卫，所以守卫只能得到`&T`，
+/// 需要修改数据时可以配合`Cell`/`RefCell`使用。
+///
+/// 执行上下文的标识由类型参数`O`提供，参见[`crate::owner`]
+/// # Exapmle
+///
+/// ```
+/// # #[cfg(feature = "std")]
+/// # {
+/// use core::cell::Cell;
+/// use xx_mutex_lock::{ReentrantMutex, ThreadOwner};
+///
+/// let locked: ReentrantMutex<_, ThreadOwner> = ReentrantMutex::new(Cell::new(1));
+/// let lock_guard1 = locked.lock();
+/// let lock_guard2 = locked.lock();
+/// lock_guard2.set(2);
+/// assert_eq!(lock_guard1.get(), 2)
+/// # }
+/// ```
+/// 当最后一个guard被drop时，自动解锁
+pub struct ReentrantMutex<T: ?Sized, O, R = Spin> {
+    /// 持有锁的上下文标识，0表示没有人持有锁
+    pub(crate) owner: AtomicUsize,
+    /// 重入次数，只有持有锁的上下文会访问
+    count: Cell<usize>,
+    _marker: PhantomData<(O, R)>,
+    data: UnsafeCell<T>,
+}
+
+/// 可重入互斥锁守卫
+/// 不能发送到其他线程，必须在上锁的上下文中drop
+pub struct ReentrantMutexGuard<'a, T: ?Sized + 'a> {
+    owner: &'a AtomicUsize,
+    count: &'a Cell<usize>,
+    data: *const T,
+}
+
+unsafe impl<T: ?Sized + Send, O, R> Sync for ReentrantMutex<T, O, R> {}
+unsafe impl<T: ?Sized + Send, O, R> Send for ReentrantMutex<T, O, R> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for ReentrantMutexGuard<'_, T> {}
+
+impl<T, O> ReentrantMutex<T, O> {
+    pub const fn new(data: T) -> Self {
+        Self::with_relax(data)
+    }
+}
+
+impl<T, O, R> ReentrantMutex<T, O, R> {
+    /// 使用指定的等待策略创建可重入互斥锁
+    pub const fn with_relax(data: T) -> Self {
+        ReentrantMutex {
+            owner: AtomicUsize::new(0),
+            count: Cell::new(0),
+            _marker: PhantomData,
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized, O: OwnerId, R> ReentrantMutex<T, O, R> {
+    /// 当前上下文是否持有这个锁
+    #[inline]
+    pub fn is_owned_by_current(&self) -> bool {
+        //只有当前上下文自己会写入自己的标识，所以Relaxed就能读到正确的结果
+        self.owner.load(Ordering::Relaxed) == O::current().get()
+    }
+
+    /// 非阻塞地上锁，锁被其他上下文持有时立即返回None
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// use xx_mutex_lock::{ReentrantMutex, ThreadOwner};
+    ///
+    /// let locked: ReentrantMutex<_, ThreadOwner> = ReentrantMutex::new(1);
+    /// let lock_guard = locked.try_lock();
+    /// assert!(lock_guard.is_some());
+    /// assert!(locked.try_lock().is_some());
+    /// # }
+    /// ```
+    #[inline]
+    pub fn try_lock(&self) -> Option<ReentrantMutexGuard<'_, T>> {
+        let me = O::current().get();
+        if self.owner.load(Ordering::Relaxed) == me {
+            return Some(self.reenter());
+        }
+        if self
+            .owner
+            .compare_exchange(0, me, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.count.set(1);
+            Some(self.guard())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn reenter(&self) -> ReentrantMutexGuard<'_, T> {
+        let count = self
+            .count
+            .get()
+            .checked_add(1)
+            .expect("ReentrantMutex lock count overflow");
+        self.count.set(count);
+        self.guard()
+    }
+
+    #[inline]
+    fn guard(&self) -> ReentrantMutexGuard<'_, T> {
+        ReentrantMutexGuard {
+            owner: &self.owner,
+            count: &self.count,
+            data: self.data.get(),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized, O: OwnerId, R: Relax> ReentrantMutex<T, O, R> {
+    /// 上锁，当前上下文已经持有锁时只增加重入次数
+    pub fn lock(&self) -> ReentrantMutexGuard<'_, T> {
+        let me = O::current().get();
+        if self.owner.load(Ordering::Relaxed) == me {
+            return self.reenter();
+        }
+        while self
+            .owner
+            .compare_exchange_weak(0, me, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            let mut relax = R::default();
+            while self.owner.load(Ordering::Relaxed) != 0 {
+                relax.relax();
+            }
+        }
+        self.count.set(1);
+        self.guard()
+    }
+}
+
+impl<'a, T: ?Sized> Deref for ReentrantMutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for ReentrantMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        let count = self.count.get() - 1;
+        self.count.set(count);
+        //最后一个守卫drop时才真正解锁
+        if count == 0 {
+            self.owner.store(0, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::reentrant_mutex::ReentrantMutex;
+    use crate::test_util::TestOwner;
+    use core::cell::Cell;
+    use core::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_reentrant() {
+        let lock: ReentrantMutex<_, TestOwner> = ReentrantMutex::new(Cell::new(0));
+        let guard1 = lock.lock();
+        let guard2 = lock.lock();
+        let guard3 = lock.try_lock().expect("err");
+        assert!(lock.is_owned_by_current());
+        guard3.set(3);
+        assert_eq!(guard1.get(), 3);
+        drop(guard1);
+        drop(guard3);
+        assert!(lock.is_owned_by_current());
+        drop(guard2);
+        assert!(!lock.is_owned_by_current());
+        assert_eq!(0, lock.owner.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test() {
+        let lock: Arc<ReentrantMutex<_, TestOwner>> = Arc::new(ReentrantMutex::new(Cell::new(0)));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        let outer = lock.lock();
+                        let inner = lock.lock();
+                        inner.set(outer.get() + 1);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(lock.lock().get(), 400);
+
+        //其他线程持有锁时try_lock失败
+        let guard = lock.lock();
+        let t_lock = lock.clone();
+        std::thread::spawn(move || assert!(t_lock.try_lock().is_none()))
+            .join()
+            .expect("err");
+        drop(guard);
+    }
+}
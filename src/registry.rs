@@ -0,0 +1,227 @@
+//! 锁的全局登记表，只在`registry` feature下可用
+//!
+//! 程序卡住时可以用[`dump_locks`]列出所有登记过的锁和它们当前的状态。
+//! [`Mutex`](crate::Mutex)、[`RWLock`](crate::RWLock)和[`OnceLock`](crate::OnceLock)
+//! 调用`register`登记自己，名字来自`named`构造函数。
+//! 登记不会撤销，所以只能登记`'static`的锁，例如静态变量或者`Box::leak`得到的锁。
+//! 登记表按地址保存在`std::sync::Mutex`里，所以这个feature需要链接std
+//! # Example
+//! ```
+//! use xx_mutex_lock::{registry, Mutex};
+//!
+//! static DEVICES: Mutex<u32> = Mutex::named("devices", 0);
+//!
+//! DEVICES.register();
+//! let _guard = DEVICES.try_lock().unwrap();
+//! for lock in registry::dump_locks() {
+//!     println!("{}", lock);
+//! }
+//! ```
+use core::{fmt, sync::atomic::Ordering};
+use std::{collections::BTreeMap, sync::Mutex as StdMutex, vec::Vec};
+
+use crate::once::status;
+use crate::sync::{AtomicBool, AtomicIsize, AtomicU8};
+
+/// [`OnceLock`](crate::OnceLock)的初始化状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnceStatus {
+    Incomplete,
+    Running,
+    Complete,
+    Panicked,
+}
+
+impl fmt::Display for OnceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OnceStatus::Incomplete => "INCOMPLETE",
+            OnceStatus::Running => "RUNNING",
+            OnceStatus::Complete => "COMPLETE",
+            OnceStatus::Panicked => "PANICKED",
+        })
+    }
+}
+
+/// 锁在[`dump_locks`]时的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockState {
+    /// 互斥锁是否被持有
+    Mutex { held: bool },
+    /// 读写锁的读者数量和是否被写者持有
+    RWLock { readers: usize, writer: bool },
+    /// 初始化的状态
+    Once(OnceStatus),
+}
+
+impl fmt::Display for LockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockState::Mutex { held: true } => f.write_str("held"),
+            LockState::Mutex { held: false } => f.write_str("free"),
+            LockState::RWLock { writer: true, .. } => f.write_str("write locked"),
+            LockState::RWLock { readers: 0, .. } => f.write_str("free"),
+            LockState::RWLock { readers, .. } => write!(f, "{} readers", readers),
+            LockState::Once(status) => fmt::Display::fmt(status, f),
+        }
+    }
+}
+
+/// 一把登记过的锁
+#[derive(Debug, Clone, Copy)]
+pub struct LockInfo {
+    /// `named`构造函数设置的名字
+    pub name: Option<&'static str>,
+    /// 锁的地址
+    pub addr: usize,
+    /// 锁的类型，例如"Mutex"
+    pub kind: &'static str,
+    pub state: LockState,
+}
+
+impl fmt::Display for LockInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} at {:#x}: {}",
+            self.kind,
+            self.name.unwrap_or("<unnamed>"),
+            self.addr,
+            self.state
+        )
+    }
+}
+
+/// 读取锁状态的原子变量，不依赖锁的类型参数
+#[derive(Clone, Copy)]
+pub(crate) enum Source {
+    Mutex(*const AtomicBool),
+    RWLock(*const AtomicIsize),
+    Once(*const AtomicU8),
+}
+
+impl Source {
+    /// 调用者保证指针有效
+    unsafe fn state(&self) -> LockState {
+        match *self {
+            Source::Mutex(lock) => LockState::Mutex {
+                held: (*lock).load(Ordering::Relaxed),
+            },
+            Source::RWLock(lock) => {
+                let state = (*lock).load(Ordering::Relaxed);
+                LockState::RWLock {
+                    readers: state.max(0) as usize,
+                    writer: state < 0,
+                }
+            }
+            Source::Once(once) => LockState::Once(match (*once).load(Ordering::Relaxed) {
+                status::INCOMPLETE => OnceStatus::Incomplete,
+                status::RUNNING => OnceStatus::Running,
+                status::COMPLETE => OnceStatus::Complete,
+                _ => OnceStatus::Panicked,
+            }),
+        }
+    }
+}
+
+struct Entry {
+    name: Option<&'static str>,
+    kind: &'static str,
+    source: Source,
+}
+
+//登记的锁都是'static的，指针一直有效
+unsafe impl Send for Entry {}
+
+/// 锁的地址到登记信息
+static REGISTRY: StdMutex<BTreeMap<usize, Entry>> = StdMutex::new(BTreeMap::new());
+
+fn registry() -> std::sync::MutexGuard<'static, BTreeMap<usize, Entry>> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 登记一把锁，报告`lock`的地址，`source`指向锁里面的状态，重复登记时什么都不做
+pub(crate) fn register<L: ?Sized>(
+    kind: &'static str,
+    name: Option<&'static str>,
+    lock: &'static L,
+    source: Source,
+) {
+    let addr = lock as *const L as *const () as usize;
+    registry()
+        .entry(addr)
+        .or_insert(Entry { name, kind, source });
+}
+
+/// 按地址顺序列出所有登记过的锁和它们当前的状态
+///
+/// 状态是逐个读取的，不是同一时刻的快照
+pub fn dump_locks() -> Vec<LockInfo> {
+    registry()
+        .iter()
+        .map(|(&addr, entry)| LockInfo {
+            name: entry.name,
+            addr,
+            kind: entry.kind,
+            state: unsafe { entry.source.state() },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::{
+        registry::{dump_locks, LockState, OnceStatus},
+        Mutex, OnceLock, RWLock,
+    };
+    use std::boxed::Box;
+
+    #[test]
+    fn test_dump_locks() {
+        static MUTEX: Mutex<i32> = Mutex::named("registry mutex", 0);
+        static CELL: OnceLock<i32> = OnceLock::named("registry cell");
+        let rw: &'static RWLock<i32> = Box::leak(Box::new(RWLock::named("registry rwlock", 0)));
+        MUTEX.register();
+        rw.register();
+        CELL.register();
+        //重复登记只列出一次
+        MUTEX.register();
+
+        let state = |name| {
+            dump_locks()
+                .into_iter()
+                .find(|lock| lock.name == Some(name))
+                .map(|lock| lock.state)
+        };
+        let _guard = MUTEX.try_lock().unwrap();
+        let _readers = (rw.try_read().unwrap(), rw.try_read().unwrap());
+        assert_eq!(
+            state("registry mutex"),
+            Some(LockState::Mutex { held: true })
+        );
+        assert_eq!(
+            state("registry rwlock"),
+            Some(LockState::RWLock {
+                readers: 2,
+                writer: false
+            })
+        );
+        assert_eq!(
+            state("registry cell"),
+            Some(LockState::Once(OnceStatus::Incomplete))
+        );
+        CELL.get_or_init(|| 1);
+        assert_eq!(
+            state("registry cell"),
+            Some(LockState::Once(OnceStatus::Complete))
+        );
+        assert_eq!(
+            dump_locks()
+                .iter()
+                .filter(|lock| lock.name == Some("registry mutex"))
+                .count(),
+            1
+        );
+    }
+}
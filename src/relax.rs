@@ -0,0 +1,93 @@
+//! 自旋等待时的让步策略
+//!
+//! 所有会自旋等待的原语（[`Mutex`](crate::Mutex)、[`RWLock`](crate::RWLock)、
+//! [`OnceLock`](crate::OnceLock)、[`LazyLock`](crate::LazyLock)）都带有一个
+//! 默认为 [`Spin`] 的类型参数 `R: Relax`，用来决定每一次等待时做什么。
+//! 每次开始等待时会用 `R::default()` 新建一个策略实例，
+//! 因此有状态的策略（例如 [`Backoff`]）可以在一次等待中逐步调整。
+use crate::sync;
+
+/// 等待策略
+///
+/// 可以自己实现这个 trait 来定制等待行为
+/// # Example
+/// ```
+/// use xx_mutex_lock::{Mutex, Relax};
+///
+/// #[derive(Default)]
+/// struct Pause;
+///
+/// impl Relax for Pause {
+///     fn relax(&mut self) {
+///         core::hint::spin_loop();
+///         core::hint::spin_loop();
+///     }
+/// }
+///
+/// let locked: Mutex<_, Pause> = Mutex::with_relax(1);
+/// assert_eq!(*locked.try_lock().unwrap(), 1);
+/// ```
+pub trait Relax: Default {
+    /// 锁不可用时调用一次，之后会重新尝试获取锁
+    fn relax(&mut self);
+}
+
+/// 忙等待，每次调用执行一次 `core::hint::spin_loop`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Spin;
+
+impl Relax for Spin {
+    #[inline(always)]
+    fn relax(&mut self) {
+        sync::spin_loop();
+    }
+}
+
+/// 指数退避，每次调用自旋的次数翻倍，直到 `2^MAX_STEP` 次
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    const MAX_STEP: u32 = 10;
+}
+
+impl Relax for Backoff {
+    #[inline]
+    fn relax(&mut self) {
+        for _ in 0..1u32 << self.step {
+            sync::spin_loop();
+        }
+        if self.step < Self::MAX_STEP {
+            self.step += 1;
+        }
+    }
+}
+
+/// 把时间片让给操作系统的其他线程，只在 `std` feature 下可用
+#[cfg(feature = "_std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Yield;
+
+#[cfg(feature = "_std")]
+impl Relax for Yield {
+    #[inline]
+    fn relax(&mut self) {
+        sync::yield_now();
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::relax::{Backoff, Relax};
+
+    #[test]
+    fn test_backoff_saturates() {
+        let mut backoff = Backoff::default();
+        for _ in 0..20 {
+            backoff.relax();
+        }
+        assert_eq!(backoff.step, Backoff::MAX_STEP);
+    }
+}
@@ -1,10 +1,33 @@
 use core::{
-    cell::UnsafeCell,
+    marker::PhantomData,
+    num::NonZeroUsize,
     //ptr::NonNull,
     ops::{Deref, DerefMut},
-    sync::atomic::{AtomicIsize, Ordering},
+    sync::atomic::Ordering,
 };
 
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+#[cfg(feature = "deadlock_detection")]
+use crate::deadlock;
+#[cfg(feature = "lockdep")]
+use crate::lockdep::{self, LockClass};
+#[cfg(debug_assertions)]
+use crate::owner::OwnerCell;
+#[cfg(feature = "std")]
+use crate::poison::{self, LockResult};
+#[cfg(feature = "registry")]
+use crate::registry::{self, Source};
+use crate::relax::{Relax, Spin};
+#[cfg(feature = "stats")]
+use crate::stats::{Histograms, Hold, LockStats, Stats};
+use crate::sync::{Access, AtomicIsize, UnsafeCell};
+#[cfg(feature = "tracing")]
+use crate::trace;
+#[cfg(feature = "watchdog")]
+use crate::watchdog::{Report, Watchdog};
+
 /// 读写锁
 /// 读写操作分离，分为了读锁和写锁，写锁将限制了仅一
 /// 个线程的临界区进行读操作，而读锁允许多个线程的临
@@ -15,7 +38,13 @@ use core::{
 ///
 /// {
 ///     let data = RWLock::new(0);
+/// #   #[cfg(feature = "std")]
+/// #   let read_lock1 = data.read().unwrap();
+/// #   #[cfg(not(feature = "std"))]
 ///     let read_lock1 = data.read();
+/// #   #[cfg(feature = "std")]
+/// #   let read_lock2 = data.read().unwrap();
+/// #   #[cfg(not(feature = "std"))]
 ///     let read_lock2 = data.read();
 ///     println!("{}", *read_lock1);
 ///     println!("{}", *read_lock2);
@@ -23,15 +52,52 @@ use core::{
 ///     drop(read_lock1);
 ///     drop(read_lock2);
 ///
+/// #   #[cfg(feature = "std")]
+/// #   let mut write_lock = data.write().unwrap();
+/// #   #[cfg(not(feature = "std"))]
 ///     let mut write_lock = data.write();
 ///     *write_lock += 1;
 /// } // 这里drop
 /// ```
-pub struct RWLock<T> {
-    pub(crate) lock: AtomicIsize,
+///
+/// 等待锁时的行为由类型参数`R`决定，默认为[`Spin`]，参见[`crate::relax`]
+///
+/// 在`std` feature下，持有写锁的线程panic会让锁中毒，
+/// 之后的`read`/`write`返回[`LockResult`]，参见[`crate::poison`]
+///
+/// 和[`Mutex`](crate::Mutex)一样，`T`可以是切片或trait对象
+///
+/// 在`lockdep` feature下会检查加锁顺序，参见[`crate::lockdep`]
+///
+/// 调试构建下会记录写锁的持有者，持有写锁时再次`read`或`write`会panic，
+/// 参见[`crate::owner`]。可以移动的`ArcRWLockWriteGuard`不记录持有者
+///
+/// 在`stats` feature下会统计获取次数和等待、持有时间，参见[`crate::stats`]
+///
+/// 在`tracing` feature下会为竞争和长时间等待发出事件，参见[`crate::trace`]
+pub struct RWLock<T: ?Sized, R = Spin> {
+    pub(crate) raw: RawRwSpin<R>,
+    #[cfg(feature = "std")]
+    poison: poison::Flag,
+    #[cfg(debug_assertions)]
+    owner: OwnerCell,
+    #[cfg(feature = "lockdep")]
+    class: LockClass,
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    name: Option<&'static str>,
+    #[cfg(feature = "stats")]
+    stats: LockStats,
     data: UnsafeCell<T>,
 }
 
+/// 不保护任何数据的读写自旋锁，[`RWLock`]的底层实现
+/// 需要手动调用加锁和解锁的方法，
+/// 在`lock_api` feature下实现了`lock_api::RawRwLock`，参见[`crate::lock_api`]
+pub struct RawRwSpin<R = Spin> {
+    pub(crate) lock: AtomicIsize,
+    _relax: PhantomData<R>,
+}
+
 /// 使用iszie保存锁的状态：
 /// 正数表示读锁，同时可以作为读锁的计数
 /// -1 表示写锁，只有一种状态
@@ -39,47 +105,304 @@ const READED: isize = 1;
 const WRITED: isize = -1;
 
 /// 读锁守卫
-pub struct RWLockReadGuard<'a, T> {
-    inner: &'a RWLock<T>,
+pub struct RWLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a AtomicIsize,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    hold: Hold<'a>,
     data: *const T,
+    access: Access,
 }
 
 /// 写锁守卫
-pub struct RWLockWriteGuard<'a, T> {
-    inner: &'a RWLock<T>,
+pub struct RWLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a AtomicIsize,
+    #[cfg(feature = "std")]
+    poison: &'a poison::Flag,
+    #[cfg(feature = "std")]
+    panicking: poison::Guard,
+    #[cfg(debug_assertions)]
+    owner: &'a OwnerCell,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    hold: Hold<'a>,
     data: *mut T,
+    access: Access,
 }
 
-unsafe impl<T: Send> Send for RWLock<T> {}
-unsafe impl<T: Send + Sync> Sync for RWLock<T> {}
+/// 映射后的读锁守卫，由[`RWLockReadGuard::map`]得到
+pub struct MappedRWLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a AtomicIsize,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    hold: Hold<'a>,
+    data: *const T,
+    access: Access,
+}
 
-impl<T> RWLock<T> {
-    pub const fn new(data: T) -> Self {
-        RWLock {
-            lock: AtomicIsize::new(0),
-            data: UnsafeCell::new(data),
+/// 映射后的写锁守卫，由[`RWLockWriteGuard::map`]得到
+pub struct MappedRWLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a AtomicIsize,
+    #[cfg(feature = "std")]
+    poison: &'a poison::Flag,
+    #[cfg(feature = "std")]
+    panicking: poison::Guard,
+    #[cfg(debug_assertions)]
+    owner: &'a OwnerCell,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    hold: Hold<'a>,
+    data: *mut T,
+    access: Access,
+}
+
+/// 持有锁的Arc的读锁守卫，由[`RWLock::read_arc`]得到，只在`alloc` feature下可用
+#[cfg(feature = "alloc")]
+pub struct ArcRWLockReadGuard<T: ?Sized, R = Spin> {
+    lock: Arc<RWLock<T, R>>,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    since: Option<u64>,
+    data: *const T,
+    access: Access,
+}
+
+/// 持有锁的Arc的写锁守卫，由[`RWLock::write_arc`]得到，只在`alloc` feature下可用
+#[cfg(feature = "alloc")]
+pub struct ArcRWLockWriteGuard<T: ?Sized, R = Spin> {
+    lock: Arc<RWLock<T, R>>,
+    #[cfg(feature = "std")]
+    panicking: poison::Guard,
+    #[cfg(feature = "lockdep")]
+    lockdep: lockdep::Entry,
+    #[cfg(feature = "stats")]
+    since: Option<u64>,
+    data: *mut T,
+    access: Access,
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Sync, R> Send for ArcRWLockReadGuard<T, R> {}
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Sync, R> Sync for ArcRWLockReadGuard<T, R> {}
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Send + Sync, R> Send for ArcRWLockWriteGuard<T, R> {}
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Sync, R> Sync for ArcRWLockWriteGuard<T, R> {}
+
+unsafe impl<T: ?Sized + Send, R> Send for RWLock<T, R> {}
+unsafe impl<T: ?Sized + Send + Sync, R> Sync for RWLock<T, R> {}
+
+impl RawRwSpin {
+    loom_const_fn! {
+        pub const fn new() -> Self {
+            Self::with_relax()
         }
     }
+}
 
-    /// 获取写锁
+impl Default for RawRwSpin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> RawRwSpin<R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建读写自旋锁
+        pub const fn with_relax() -> Self {
+            RawRwSpin {
+                lock: AtomicIsize::new(0),
+                _relax: PhantomData,
+            }
+        }
+    }
+
+    /// 是否有读者或写者持有锁，只用于调试，返回值可能立即过时
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.lock.load(Ordering::Relaxed) != 0
+    }
+
+    /// 是否有写者持有锁，只用于调试，返回值可能立即过时
+    #[inline]
+    pub fn is_locked_exclusive(&self) -> bool {
+        self.lock.load(Ordering::Relaxed) < 0
+    }
+
+    /// 非阻塞地获取写锁，成功时返回true
+    #[inline]
+    pub fn try_write(&self) -> bool {
+        self.write_request()
+    }
+
+    /// 非阻塞地获取读锁，成功时返回true
+    #[inline]
+    pub fn try_read(&self) -> bool {
+        self.read_request() > 0
+    }
+
+    /// 释放读锁
+    ///
+    /// # Safety
+    /// 只能由持有读锁的一方调用
+    #[inline]
+    pub unsafe fn unlock_read(&self) {
+        self.lock.fetch_sub(READED, Ordering::Release);
+    }
+
+    /// 释放写锁
+    ///
+    /// # Safety
+    /// 只能由持有写锁的一方调用
+    #[inline]
+    pub unsafe fn unlock_write(&self) {
+        self.lock.fetch_sub(WRITED, Ordering::Release);
+    }
+
+    /// 把持有的写锁原子地降级为读锁，期间其他写者不能插入
+    ///
+    /// # Safety
+    /// 只能由持有写锁的一方调用
     #[inline]
-    pub fn write(&self) -> RWLockWriteGuard<T> {
+    pub unsafe fn downgrade(&self) {
+        self.lock.store(READED, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn write_request(&self) -> bool {
+        self.lock
+            .compare_exchange(0, WRITED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// 申请读锁，成功时返回申请后的读者数量，失败时返回 -1
+    /// 读者计数的检查和增加在同一次 compare_exchange 中完成，
+    /// 失败时不会修改锁的状态
+    #[inline]
+    pub(crate) fn read_request(&self) -> isize {
+        const MAX_READERS: isize = isize::MAX;
+        let mut readers = self.lock.load(Ordering::Relaxed);
+
         loop {
-            match self.try_write() {
-                Some(guard) => return guard,
-                None => continue,
+            if readers == MAX_READERS || readers < 0 {
+                return -1;
+            }
+            match self.lock.compare_exchange_weak(
+                readers,
+                readers + READED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return readers + READED,
+                //其他线程修改了状态，用最新的值重试
+                Err(current) => readers = current,
+            }
+        }
+    }
+}
+
+impl<R: Relax> RawRwSpin<R> {
+    /// 获取写锁，锁被持有时按照`R`的策略等待
+    #[inline]
+    pub fn write(&self) {
+        self.write_with(|| ())
+    }
+
+    /// 获取读锁，有写者时按照`R`的策略等待
+    #[inline]
+    pub fn read(&self) {
+        self.read_with(|| ())
+    }
+
+    /// 获取写锁，每次等待后调用一次`on_relax`
+    #[inline]
+    pub(crate) fn write_with(&self, mut on_relax: impl FnMut()) {
+        let mut relax = R::default();
+        while !self.try_write() {
+            relax.relax();
+            on_relax();
+        }
+    }
+
+    /// 获取读锁，每次等待后调用一次`on_relax`
+    #[inline]
+    pub(crate) fn read_with(&self, mut on_relax: impl FnMut()) {
+        let mut relax = R::default();
+        while !self.try_read() {
+            relax.relax();
+            on_relax();
+        }
+    }
+}
+
+impl<T> RWLock<T> {
+    loom_const_fn! {
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn new(data: T) -> Self {
+            Self::with_relax(data)
+        }
+    }
+
+    loom_const_fn! {
+        /// 创建带名字的读写锁，名字只用于诊断输出，参见[`Mutex::named`](crate::Mutex::named)
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        #[allow(unused_variables)]
+        pub const fn named(name: &'static str, data: T) -> Self {
+            #[allow(unused_mut)]
+            let mut lock = Self::with_relax(data);
+            #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+            {
+                lock.name = Some(name);
             }
+            lock
         }
     }
+}
 
+impl<T, R> RWLock<T, R> {
+    loom_const_fn! {
+        /// 使用指定的等待策略创建读写锁
+        /// # Example
+        /// ```
+        /// use xx_mutex_lock::{Backoff, RWLock};
+        ///
+        /// let data: RWLock<_, Backoff> = RWLock::with_relax(1);
+        /// assert_eq!(*data.try_read().unwrap(), 1);
+        /// ```
+        #[cfg_attr(feature = "lockdep", track_caller)]
+        pub const fn with_relax(data: T) -> Self {
+            RWLock {
+                raw: RawRwSpin::with_relax(),
+                #[cfg(feature = "std")]
+                poison: poison::Flag::new(),
+                #[cfg(debug_assertions)]
+                owner: OwnerCell::new(),
+                #[cfg(feature = "lockdep")]
+                class: LockClass::caller(),
+                #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+                name: None,
+                #[cfg(feature = "stats")]
+                stats: LockStats::new(),
+                data: UnsafeCell::new(data),
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, R> RWLock<T, R> {
     /// 非阻塞地获取写锁
     #[inline]
-    pub fn try_write(&self) -> Option<RWLockWriteGuard<T>> {
+    pub fn try_write(&self) -> Option<RWLockWriteGuard<'_, T>> {
         if self.write_request() {
-            Some(RWLockWriteGuard {
-                inner: self,
-                data: self.data.get(),
-            })
+            #[cfg(feature = "stats")]
+            self.stats.acquired(0, None);
+            Some(self.write_guard())
         } else {
             None
         }
@@ -87,87 +410,711 @@ impl<T> RWLock<T> {
 
     #[inline]
     fn write_request(&self) -> bool {
-        self.lock
-            .compare_exchange(0, WRITED, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+        self.raw.write_request()
+    }
+
+    /// 非阻塞地获取读锁
+    #[inline]
+    pub fn try_read(&self) -> Option<RWLockReadGuard<'_, T>> {
+        if self.read_request() > 0 {
+            #[cfg(feature = "stats")]
+            self.stats.acquired(0, None);
+            Some(self.read_guard())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn read_request(&self) -> isize {
+        self.raw.read_request()
+    }
+
+    #[cfg(any(feature = "watchdog", feature = "tracing"))]
+    #[inline]
+    fn addr(&self) -> *const () {
+        self as *const Self as *const ()
+    }
+
+    #[inline]
+    fn write_guard(&self) -> RWLockWriteGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        let entry = lockdep::acquired(&self.raw.lock, self.class);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::acquired(&self.raw.lock);
+        #[cfg(debug_assertions)]
+        self.owner.set();
+        let (data, access) = self.data.get();
+        RWLockWriteGuard {
+            lock: &self.raw.lock,
+            #[cfg(feature = "std")]
+            poison: &self.poison,
+            #[cfg(all(feature = "std", not(feature = "tracing")))]
+            panicking: self.poison.guard(),
+            #[cfg(all(feature = "std", feature = "tracing"))]
+            panicking: self.poison.guard(trace::LockId {
+                kind: "RWLock",
+                name: self.name,
+                addr: self.addr(),
+            }),
+            #[cfg(debug_assertions)]
+            owner: &self.owner,
+            #[cfg(feature = "lockdep")]
+            lockdep: entry,
+            #[cfg(feature = "stats")]
+            hold: self.stats.hold(),
+            data,
+            access,
+        }
+    }
+
+    #[inline]
+    fn read_guard(&self) -> RWLockReadGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        let entry = lockdep::acquired(&self.raw.lock, self.class);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::acquired(&self.raw.lock);
+        let (data, access) = self.data.get_shared();
+        RWLockReadGuard {
+            lock: &self.raw.lock,
+            #[cfg(feature = "lockdep")]
+            lockdep: entry,
+            #[cfg(feature = "stats")]
+            hold: self.stats.hold(),
+            data,
+            access,
+        }
+    }
+
+    /// 锁是否中毒，即是否有线程在持有写锁时panic
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// 清除中毒标记，调用者需要保证数据已经恢复到一致的状态
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.poison.clear()
+    }
+
+    /// 持有写锁的上下文的标识，只在调试构建下记录，参见[`crate::owner`]
+    #[cfg(debug_assertions)]
+    pub fn owner(&self) -> Option<NonZeroUsize> {
+        self.owner.get()
+    }
+
+    /// 持有写锁的上下文的标识，只在调试构建下记录，这里总是返回None
+    #[cfg(not(debug_assertions))]
+    pub fn owner(&self) -> Option<NonZeroUsize> {
+        None
+    }
+
+    /// 由[`RWLock::named`]设置的名字
+    #[cfg(any(feature = "watchdog", feature = "tracing", feature = "registry"))]
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// 锁的统计快照，读锁和写锁一起统计，参见[`crate::stats`]
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// 清零统计信息
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset()
+    }
+
+    /// 挂上记录等待时间和持有时间的直方图，参见[`crate::stats::Histograms`]
+    #[cfg(feature = "stats")]
+    pub fn set_histograms(&self, histograms: &'static Histograms) {
+        self.stats.set_histograms(histograms)
+    }
+
+    /// 登记到全局登记表，登记不会撤销，所以锁必须是`'static`的，参见[`crate::registry`]
+    #[cfg(feature = "registry")]
+    pub fn register(&'static self) {
+        registry::register("RWLock", self.name, self, Source::RWLock(&self.raw.lock))
+    }
+
+    /// 锁所属的锁类，默认是创建锁的位置
+    #[cfg(feature = "lockdep")]
+    pub fn lock_class(&self) -> LockClass {
+        self.class
+    }
+
+    /// 修改锁所属的锁类
+    #[cfg(feature = "lockdep")]
+    pub fn set_lock_class(&mut self, class: LockClass) {
+        self.class = class;
+    }
+}
+
+impl<T: ?Sized, R: Relax> RWLock<T, R> {
+    /// 获取写锁
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn write(&self) -> RWLockWriteGuard<'_, T> {
+        self.acquire_write()
+    }
+
+    /// 获取写锁，锁中毒时返回[`PoisonError`](crate::poison::PoisonError)
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write(&self) -> LockResult<RWLockWriteGuard<'_, T>> {
+        let guard = self.acquire_write();
+        poison::map_result(self.poison.get(), guard)
     }
 
     /// 获取读锁
+    #[cfg(not(feature = "std"))]
     #[inline]
-    pub fn read(&self) -> RWLockReadGuard<T> {
-        loop {
-            match self.try_read() {
-                Some(guard) => return guard,
-                None => continue,
+    pub fn read(&self) -> RWLockReadGuard<'_, T> {
+        self.acquire_read()
+    }
+
+    /// 获取读锁，锁中毒时返回[`PoisonError`](crate::poison::PoisonError)
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn read(&self) -> LockResult<RWLockReadGuard<'_, T>> {
+        let guard = self.acquire_read();
+        poison::map_result(self.poison.get(), guard)
+    }
+
+    #[inline]
+    fn acquire_write(&self) -> RWLockWriteGuard<'_, T> {
+        #[cfg(debug_assertions)]
+        self.owner.check("RWLock", self);
+        #[cfg(feature = "lockdep")]
+        lockdep::check(&self.raw.lock, self.class);
+        #[cfg(feature = "deadlock_detection")]
+        let waiting = deadlock::wait(&self.raw.lock);
+        #[cfg(feature = "stats")]
+        let start = self.stats.start();
+        #[cfg(any(feature = "stats", feature = "tracing"))]
+        let mut spins = 0;
+        #[cfg(feature = "watchdog")]
+        let mut watchdog = Watchdog::new();
+        self.raw.write_with(|| {
+            #[cfg(any(feature = "stats", feature = "tracing"))]
+            {
+                spins += 1;
             }
+            #[cfg(feature = "tracing")]
+            trace::waiting("RWLock", self.name, self.addr(), spins);
+            #[cfg(feature = "watchdog")]
+            watchdog.tick(|spins| self.report(spins));
+        });
+        #[cfg(feature = "deadlock_detection")]
+        drop(waiting);
+        #[cfg(feature = "stats")]
+        self.stats.acquired(spins, start);
+        #[cfg(feature = "tracing")]
+        if spins != 0 {
+            trace::contended("RWLock", self.name, self.addr(), spins);
         }
+        self.write_guard()
     }
 
-    /// 非阻塞地获取读锁
     #[inline]
-    pub fn try_read(&self) -> Option<RWLockReadGuard<T>> {
-        if self.read_request() >= 0 {
-            Some(RWLockReadGuard {
-                inner: self,
-                data: self.data.get(),
-            })
-        } else {
-            None
+    fn acquire_read(&self) -> RWLockReadGuard<'_, T> {
+        #[cfg(debug_assertions)]
+        self.owner.check("RWLock", self);
+        #[cfg(feature = "lockdep")]
+        lockdep::check(&self.raw.lock, self.class);
+        #[cfg(feature = "deadlock_detection")]
+        let waiting = deadlock::wait(&self.raw.lock);
+        #[cfg(feature = "stats")]
+        let start = self.stats.start();
+        #[cfg(any(feature = "stats", feature = "tracing"))]
+        let mut spins = 0;
+        #[cfg(feature = "watchdog")]
+        let mut watchdog = Watchdog::new();
+        self.raw.read_with(|| {
+            #[cfg(any(feature = "stats", feature = "tracing"))]
+            {
+                spins += 1;
+            }
+            #[cfg(feature = "tracing")]
+            trace::waiting("RWLock", self.name, self.addr(), spins);
+            #[cfg(feature = "watchdog")]
+            watchdog.tick(|spins| self.report(spins));
+        });
+        #[cfg(feature = "deadlock_detection")]
+        drop(waiting);
+        #[cfg(feature = "stats")]
+        self.stats.acquired(spins, start);
+        #[cfg(feature = "tracing")]
+        if spins != 0 {
+            trace::contended("RWLock", self.name, self.addr(), spins);
+        }
+        self.read_guard()
+    }
+
+    #[cfg(feature = "watchdog")]
+    #[cold]
+    fn report(&self, spins: u64) -> Report {
+        Report {
+            lock: self.addr() as usize,
+            kind: "RWLock",
+            name: self.name,
+            state: self.raw.lock.load(Ordering::Relaxed),
+            owner: self.owner(),
+            spins,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RWLockReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RWLockWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RWLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(self.lock);
+        #[cfg(feature = "stats")]
+        self.hold.release();
+        self.access.end();
+        self.lock.fetch_sub(READED, Ordering::Release);
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RWLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        self.poison.done(&self.panicking);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(self.lock);
+        #[cfg(debug_assertions)]
+        self.owner.clear();
+        #[cfg(feature = "stats")]
+        self.hold.release();
+        self.access.end();
+        self.lock.fetch_sub(WRITED, Ordering::Release);
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RWLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized> RWLockReadGuard<'a, T> {
+    /// 把读锁守卫映射到被保护数据的一部分上，读锁在返回的守卫drop时才释放
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::{RWLock, RWLockReadGuard};
+    ///
+    /// let data = RWLock::new((1, 2));
+    /// let read_lock = data.try_read().unwrap();
+    /// let second = RWLockReadGuard::map(read_lock, |data| &data.1);
+    /// assert_eq!(*second, 2);
+    /// assert!(data.try_write().is_none());
+    /// ```
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedRWLockReadGuard<'a, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let data = f(unsafe { &*orig.data }) as *const U;
+        Self::into_mapped(orig, data)
+    }
+
+    /// 和[`RWLockReadGuard::map`]一样，但闭包返回None时把原来的守卫还回去
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedRWLockReadGuard<'a, U>, Self>
+    where
+        F: FnOnce(&T) -> Option<&U>,
+    {
+        match f(unsafe { &*orig.data }) {
+            Some(data) => {
+                let data = data as *const U;
+                Ok(Self::into_mapped(orig, data))
+            }
+            None => Err(orig),
         }
     }
 
     #[inline]
-    fn read_request(&self) -> isize {
-        const MAX_READERS: isize = core::isize::MAX;
-        let mut readers = self.lock.load(Ordering::Acquire);
+    fn into_mapped<U: ?Sized>(mut orig: Self, data: *const U) -> MappedRWLockReadGuard<'a, U> {
+        let mapped = MappedRWLockReadGuard {
+            lock: orig.lock,
+            #[cfg(feature = "lockdep")]
+            lockdep: orig.lockdep,
+            #[cfg(feature = "stats")]
+            hold: orig.hold,
+            data,
+            access: core::mem::take(&mut orig.access),
+        };
+        //读锁的所有权转移到了新的守卫上
+        core::mem::forget(orig);
+        mapped
+    }
+}
 
-        if readers == MAX_READERS || readers < 0 {
-            // panic!("read request wrong");
-            -1
-        } else {
-            readers = self.lock.fetch_add(READED, Ordering::Relaxed);
-            readers
+impl<'a, T: ?Sized> RWLockWriteGuard<'a, T> {
+    /// 把写锁守卫映射到被保护数据的一部分上，写锁在返回的守卫drop时才释放
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::{RWLock, RWLockWriteGuard};
+    ///
+    /// let data = RWLock::new((1, 2));
+    /// let write_lock = data.try_write().unwrap();
+    /// let mut second = RWLockWriteGuard::map(write_lock, |data| &mut data.1);
+    /// *second += 1;
+    /// drop(second);
+    /// assert_eq!(*data.try_read().unwrap(), (1, 3));
+    /// ```
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedRWLockWriteGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let data = f(unsafe { &mut *orig.data }) as *mut U;
+        Self::into_mapped(orig, data)
+    }
+
+    /// 和[`RWLockWriteGuard::map`]一样，但闭包返回None时把原来的守卫还回去
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedRWLockWriteGuard<'a, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        match f(unsafe { &mut *orig.data }) {
+            Some(data) => {
+                let data = data as *mut U;
+                Ok(Self::into_mapped(orig, data))
+            }
+            None => Err(orig),
         }
     }
+
+    #[inline]
+    fn into_mapped<U: ?Sized>(mut orig: Self, data: *mut U) -> MappedRWLockWriteGuard<'a, U> {
+        let mapped = MappedRWLockWriteGuard {
+            lock: orig.lock,
+            #[cfg(feature = "std")]
+            poison: orig.poison,
+            #[cfg(feature = "std")]
+            panicking: orig.panicking,
+            #[cfg(debug_assertions)]
+            owner: orig.owner,
+            #[cfg(feature = "lockdep")]
+            lockdep: orig.lockdep,
+            #[cfg(feature = "stats")]
+            hold: orig.hold,
+            data,
+            access: core::mem::take(&mut orig.access),
+        };
+        //写锁的所有权转移到了新的守卫上
+        core::mem::forget(orig);
+        mapped
+    }
 }
 
-impl<'a, T> Deref for RWLockReadGuard<'a, T> {
+impl<'a, T: ?Sized> Deref for MappedRWLockReadGuard<'a, T> {
     type Target = T;
     fn deref(&self) -> &T {
         unsafe { &*self.data }
     }
 }
 
-impl<'a, T> Deref for RWLockWriteGuard<'a, T> {
+impl<'a, T: ?Sized> Deref for MappedRWLockWriteGuard<'a, T> {
     type Target = T;
     fn deref(&self) -> &T {
         unsafe { &*self.data }
     }
 }
 
-impl<'a, T> Drop for RWLockReadGuard<'a, T> {
+impl<'a, T: ?Sized> DerefMut for MappedRWLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for MappedRWLockReadGuard<'a, T> {
     fn drop(&mut self) {
-        self.inner.lock.fetch_sub(READED, Ordering::Release);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(self.lock);
+        #[cfg(feature = "stats")]
+        self.hold.release();
+        self.access.end();
+        self.lock.fetch_sub(READED, Ordering::Release);
     }
 }
 
-impl<'a, T> Drop for RWLockWriteGuard<'a, T> {
+impl<'a, T: ?Sized> Drop for MappedRWLockWriteGuard<'a, T> {
     fn drop(&mut self) {
-        self.inner.lock.fetch_sub(WRITED, Ordering::Release);
+        #[cfg(feature = "std")]
+        self.poison.done(&self.panicking);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(self.lock);
+        #[cfg(debug_assertions)]
+        self.owner.clear();
+        #[cfg(feature = "stats")]
+        self.hold.release();
+        self.access.end();
+        self.lock.fetch_sub(WRITED, Ordering::Release);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> RWLock<T, R> {
+    /// 非阻塞地获取读锁，得到持有锁的Arc的守卫
+    #[inline]
+    pub fn try_read_arc(self: &Arc<Self>) -> Option<ArcRWLockReadGuard<T, R>> {
+        self.try_read().map(|guard| self.into_arc_read_guard(guard))
+    }
+
+    /// 非阻塞地获取写锁，得到持有锁的Arc的守卫
+    #[inline]
+    pub fn try_write_arc(self: &Arc<Self>) -> Option<ArcRWLockWriteGuard<T, R>> {
+        self.try_write()
+            .map(|guard| self.into_arc_write_guard(guard))
+    }
+
+    #[inline]
+    fn into_arc_read_guard(
+        self: &Arc<Self>,
+        mut guard: RWLockReadGuard<'_, T>,
+    ) -> ArcRWLockReadGuard<T, R> {
+        let arc_guard = ArcRWLockReadGuard {
+            lock: self.clone(),
+            #[cfg(feature = "lockdep")]
+            lockdep: guard.lockdep,
+            #[cfg(feature = "stats")]
+            since: guard.hold.since,
+            data: guard.data,
+            access: core::mem::take(&mut guard.access),
+        };
+        //读锁的所有权转移到了新的守卫上
+        core::mem::forget(guard);
+        arc_guard
+    }
+
+    #[inline]
+    fn into_arc_write_guard(
+        self: &Arc<Self>,
+        mut guard: RWLockWriteGuard<'_, T>,
+    ) -> ArcRWLockWriteGuard<T, R> {
+        let arc_guard = ArcRWLockWriteGuard {
+            lock: self.clone(),
+            #[cfg(feature = "std")]
+            panicking: guard.panicking,
+            #[cfg(feature = "lockdep")]
+            lockdep: guard.lockdep,
+            #[cfg(feature = "stats")]
+            since: guard.hold.since,
+            data: guard.data,
+            access: core::mem::take(&mut guard.access),
+        };
+        //Arc守卫可以移动到其他线程，不再记录持有者
+        #[cfg(debug_assertions)]
+        self.owner.clear();
+        //写锁的所有权转移到了新的守卫上
+        core::mem::forget(guard);
+        arc_guard
     }
 }
 
-impl<'a, T> DerefMut for RWLockWriteGuard<'a, T> {
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R: Relax> RWLock<T, R> {
+    /// 获取读锁，得到持有锁的Arc的守卫
+    /// # Example
+    /// ```
+    /// use std::sync::Arc;
+    /// use xx_mutex_lock::RWLock;
+    ///
+    /// let data = Arc::new(RWLock::new(1));
+    /// # #[cfg(feature = "std")]
+    /// # let read_lock = data.read_arc().unwrap();
+    /// # #[cfg(not(feature = "std"))]
+    /// let read_lock = data.read_arc();
+    /// std::thread::spawn(move || assert_eq!(*read_lock, 1)).join().unwrap();
+    /// assert!(data.try_write().is_some());
+    /// ```
+    #[cfg(not(feature = "std"))]
+    pub fn read_arc(self: &Arc<Self>) -> ArcRWLockReadGuard<T, R> {
+        let guard = self.acquire_read();
+        self.into_arc_read_guard(guard)
+    }
+
+    /// 获取读锁，得到持有锁的Arc的守卫，锁中毒时返回[`PoisonError`](crate::poison::PoisonError)
+    #[cfg(feature = "std")]
+    pub fn read_arc(self: &Arc<Self>) -> LockResult<ArcRWLockReadGuard<T, R>> {
+        let guard = self.acquire_read();
+        let guard = self.into_arc_read_guard(guard);
+        poison::map_result(self.poison.get(), guard)
+    }
+
+    /// 获取写锁，得到持有锁的Arc的守卫
+    #[cfg(not(feature = "std"))]
+    pub fn write_arc(self: &Arc<Self>) -> ArcRWLockWriteGuard<T, R> {
+        let guard = self.acquire_write();
+        self.into_arc_write_guard(guard)
+    }
+
+    /// 获取写锁，得到持有锁的Arc的守卫，锁中毒时返回[`PoisonError`](crate::poison::PoisonError)
+    #[cfg(feature = "std")]
+    pub fn write_arc(self: &Arc<Self>) -> LockResult<ArcRWLockWriteGuard<T, R>> {
+        let guard = self.acquire_write();
+        let guard = self.into_arc_write_guard(guard);
+        poison::map_result(self.poison.get(), guard)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> ArcRWLockReadGuard<T, R> {
+    /// 守卫对应的锁
+    pub fn rwlock(guard: &Self) -> &Arc<RWLock<T, R>> {
+        &guard.lock
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> ArcRWLockWriteGuard<T, R> {
+    /// 守卫对应的锁
+    pub fn rwlock(guard: &Self) -> &Arc<RWLock<T, R>> {
+        &guard.lock
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> Deref for ArcRWLockReadGuard<T, R> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> Deref for ArcRWLockWriteGuard<T, R> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> DerefMut for ArcRWLockWriteGuard<T, R> {
     fn deref_mut(&mut self) -> &mut T {
         unsafe { &mut *self.data }
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> Drop for ArcRWLockReadGuard<T, R> {
+    fn drop(&mut self) {
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(&self.lock.raw.lock);
+        #[cfg(feature = "stats")]
+        self.lock.stats.released(self.since);
+        self.access.end();
+        unsafe { self.lock.raw.unlock_read() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R> Drop for ArcRWLockWriteGuard<T, R> {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        self.lock.poison.done(&self.panicking);
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.lockdep);
+        #[cfg(feature = "deadlock_detection")]
+        deadlock::release(&self.lock.raw.lock);
+        #[cfg(feature = "stats")]
+        self.lock.stats.released(self.since);
+        self.access.end();
+        unsafe { self.lock.raw.unlock_write() }
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawRwLock for RawRwSpin<R> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = RawRwSpin::with_relax();
+
+    type GuardMarker = ::lock_api::GuardSend;
+
+    #[inline]
+    fn lock_shared(&self) {
+        self.read()
+    }
+
+    #[inline]
+    fn try_lock_shared(&self) -> bool {
+        self.try_read()
+    }
+
+    #[inline]
+    unsafe fn unlock_shared(&self) {
+        self.unlock_read()
+    }
+
+    #[inline]
+    fn lock_exclusive(&self) {
+        self.write()
+    }
+
+    #[inline]
+    fn try_lock_exclusive(&self) -> bool {
+        self.try_write()
+    }
+
+    #[inline]
+    unsafe fn unlock_exclusive(&self) {
+        self.unlock_write()
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        RawRwSpin::is_locked(self)
+    }
+
+    #[inline]
+    fn is_locked_exclusive(&self) -> bool {
+        RawRwSpin::is_locked_exclusive(self)
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawRwLockDowngrade for RawRwSpin<R> {
+    #[inline]
+    unsafe fn downgrade(&self) {
+        RawRwSpin::downgrade(self)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     extern crate std;
 
     use crate::rw_lock::RWLock;
+    use crate::test_util::unpoison;
     //use std::println;
 
     #[test]
@@ -211,9 +1158,12 @@ pub mod test {
         assert!(m.try_read().is_none());
         drop(wlock);
 
+        let mut guards = std::vec::Vec::new();
         let mut i = 0;
         while i < 100 {
-            assert!(m.try_read().is_some());
+            let guard = m.try_read();
+            assert!(guard.is_some());
+            guards.push(guard);
             i += 1;
         }
 
@@ -223,8 +1173,8 @@ pub mod test {
     #[test]
     fn test() {
         let data = RWLock::new(0);
-        let read_lock1 = data.read();
-        let read_lock2 = data.read();
+        let read_lock1 = unpoison(data.read());
+        let read_lock2 = unpoison(data.read());
 
         assert_eq!(0, *read_lock1);
         assert_eq!(0, *read_lock2);
@@ -234,7 +1184,7 @@ pub mod test {
         drop(read_lock1);
         drop(read_lock2);
 
-        let mut write_lock = data.write();
+        let mut write_lock = unpoison(data.write());
         *write_lock += 1;
 
         assert!(data.try_write().is_none());
@@ -242,4 +1192,237 @@ pub mod test {
 
         assert_eq!(1, *write_lock);
     }
+
+    #[test]
+    fn test_rw_concurrent_exclusion() {
+        use std::sync::atomic::{AtomicIsize, Ordering};
+        use std::sync::Arc;
+        use std::vec::Vec;
+
+        //正数表示正在读的线程数，-1 表示有线程正在写
+        let data = Arc::new(RWLock::new(0usize));
+        let holders = Arc::new(AtomicIsize::new(0));
+        let mut threads = Vec::new();
+
+        for _ in 0..4 {
+            let data = data.clone();
+            let holders = holders.clone();
+            threads.push(std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    let mut guard = unpoison(data.write());
+                    assert_eq!(0, holders.swap(-1, Ordering::SeqCst));
+                    *guard += 1;
+                    assert_eq!(-1, holders.swap(0, Ordering::SeqCst));
+                }
+            }));
+        }
+
+        for _ in 0..4 {
+            let data = data.clone();
+            let holders = holders.clone();
+            threads.push(std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    let guard = unpoison(data.read());
+                    assert!(holders.fetch_add(1, Ordering::SeqCst) >= 0);
+                    let _ = *guard;
+                    assert!(holders.fetch_sub(1, Ordering::SeqCst) > 0);
+                }
+            }));
+        }
+
+        for t in threads {
+            t.join().expect("Err");
+        }
+
+        assert_eq!(0, data.raw.lock.load(Ordering::Relaxed));
+        assert_eq!(4000, *unpoison(data.read()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_poison() {
+        use std::sync::Arc;
+        let data = Arc::new(RWLock::new(0));
+
+        //读锁持有期间panic不会让锁中毒
+        let t_data = data.clone();
+        let res = std::thread::spawn(move || {
+            let _guard = t_data.read().unwrap();
+            panic!("panic while reading");
+        })
+        .join();
+        assert!(res.is_err());
+        assert!(!data.is_poisoned());
+
+        let t_data = data.clone();
+        let res = std::thread::spawn(move || {
+            let mut guard = t_data.write().unwrap();
+            *guard += 1;
+            panic!("panic while writing");
+        })
+        .join();
+        assert!(res.is_err());
+        assert!(data.is_poisoned());
+        assert!(data.read().is_err());
+        assert!(data.write().is_err());
+        assert_eq!(*data.read().unwrap_or_else(|e| e.into_inner()), 1);
+        assert_eq!(*data.write().unwrap_or_else(|e| e.into_inner()), 1);
+
+        data.clear_poison();
+        assert!(!data.is_poisoned());
+        assert_eq!(*data.read().unwrap(), 1);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_recursive_write() {
+        use crate::owner::OwnerId;
+        use crate::test_util::{track_owner, TestOwner};
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        track_owner();
+        let data = RWLock::new(0);
+        let read = unpoison(data.read());
+        assert_eq!(data.owner(), None);
+        drop(read);
+
+        let write = unpoison(data.write());
+        assert_eq!(data.owner(), Some(TestOwner::current()));
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(data.read()))).is_err());
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(data.write()))).is_err());
+        drop(write);
+        assert_eq!(data.owner(), None);
+        assert!(data.try_write().is_some());
+    }
+
+    #[cfg(all(debug_assertions, feature = "alloc"))]
+    #[test]
+    fn test_moved_arc_write_guard() {
+        use crate::test_util::track_owner;
+        use std::sync::Arc;
+        track_owner();
+        let data = Arc::new(RWLock::new(0));
+        let mut write = unpoison(data.write_arc());
+        assert_eq!(data.owner(), None);
+
+        //守卫移动到另一个线程之后，原来的线程等待而不是panic
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            *write += 1;
+        });
+        assert_eq!(*unpoison(data.read()), 1);
+        handle.join().expect("err");
+    }
+
+    #[test]
+    fn test_map() {
+        use crate::rw_lock::{RWLockReadGuard, RWLockWriteGuard};
+        use core::sync::atomic::Ordering;
+        let data = RWLock::new((1, [0u8; 4]));
+
+        let write_lock = unpoison(data.write());
+        let Err(write_lock) = RWLockWriteGuard::try_map(write_lock, |d| d.1.get_mut(10)) else {
+            panic!("index out of range");
+        };
+        let mut bytes = RWLockWriteGuard::map(write_lock, |d| &mut d.1);
+        bytes[1] = 5;
+        assert!(data.try_read().is_none());
+        drop(bytes);
+        assert_eq!(0, data.raw.lock.load(Ordering::Relaxed));
+
+        let read_lock1 = unpoison(data.read());
+        let read_lock2 = unpoison(data.read());
+        let first = RWLockReadGuard::map(read_lock1, |d| &d.0);
+        let Ok(byte) = RWLockReadGuard::try_map(read_lock2, |d| d.1.get(1)) else {
+            panic!("err");
+        };
+        assert_eq!(2, data.raw.lock.load(Ordering::Relaxed));
+        assert_eq!(1, *first);
+        assert_eq!(5, *byte);
+        assert!(data.try_write().is_none());
+        drop(first);
+        drop(byte);
+        assert_eq!(0, data.raw.lock.load(Ordering::Relaxed));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_arc_guard() {
+        use core::sync::atomic::Ordering;
+        use std::sync::Arc;
+
+        let data = Arc::new(RWLock::new(0));
+        let read_lock1 = unpoison(data.read_arc());
+        let read_lock2 = data.try_read_arc().expect("err");
+        assert!(data.try_write_arc().is_none());
+        assert_eq!(2, data.raw.lock.load(Ordering::Relaxed));
+
+        //读锁守卫可以移动到其他线程中释放
+        std::thread::spawn(move || {
+            assert_eq!(0, *read_lock1);
+            drop(read_lock1);
+        })
+        .join()
+        .expect("err");
+        drop(read_lock2);
+
+        let mut write_lock = unpoison(data.write_arc());
+        assert!(data.try_read_arc().is_none());
+        std::thread::spawn(move || {
+            *write_lock += 1;
+        })
+        .join()
+        .expect("err");
+        assert_eq!(0, data.raw.lock.load(Ordering::Relaxed));
+        assert_eq!(1, *data.try_read_arc().expect("err"));
+        assert_eq!(1, Arc::strong_count(&data));
+    }
+
+    #[test]
+    fn test_unsized() {
+        use core::fmt::Debug;
+        use std::boxed::Box;
+        use std::format;
+
+        let bytes = RWLock::new([0u8; 4]);
+        let slice: &RWLock<[u8]> = &bytes;
+        unpoison(slice.write())[1] = 1;
+        assert_eq!(unpoison(slice.read()).len(), 4);
+        assert_eq!(*unpoison(bytes.read()), [0, 1, 0, 0]);
+
+        let boxed: Box<RWLock<dyn Debug>> = Box::new(RWLock::new(1));
+        assert_eq!("1", format!("{:?}", &*unpoison(boxed.read())));
+    }
+}
+
+#[cfg(all(test, loom))]
+pub mod loom_test {
+    extern crate std;
+    use crate::{test_util::unpoison, RWLock};
+    use loom::{sync::Arc, thread};
+
+    #[test]
+    fn test_reader_writer_exclusion() {
+        loom::model(|| {
+            let lock = Arc::new(RWLock::new((0, 0)));
+            let writer = {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    let mut data = unpoison(lock.write());
+                    data.0 += 1;
+                    data.1 += 1;
+                })
+            };
+            let reader = {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    //读者不会看到写了一半的数据
+                    let data = unpoison(lock.read());
+                    assert_eq!(data.0, data.1);
+                })
+            };
+            writer.join().expect("Err");
+            reader.join().expect("Err");
+            assert_eq!(*unpoison(lock.read()), (1, 1));
+        });
+    }
 }
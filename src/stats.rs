@@ -0,0 +1,300 @@
+//! 锁的统计信息，只在`stats` feature下可用
+//!
+//! [`Mutex`](crate::Mutex)和[`RWLock`](crate::RWLock)会记录获取次数、
+//! 需要等待的获取次数和等待时的自旋次数，通过`stats()`得到快照，`reset_stats()`清零。
+//!
+//! 等待时间和持有时间的直方图比较大，不放在锁里。
+//! 需要时用`set_histograms`给锁挂上一个静态的[`Histograms`]，
+//! 并用[`crate::clock::set_clock`]设置时钟，之后才会计时
+//! # Example
+//! ```
+//! use xx_mutex_lock::Mutex;
+//!
+//! let locked = Mutex::new(1);
+//! drop(locked.try_lock());
+//! drop(locked.try_lock());
+//! let stats = locked.stats();
+//! assert_eq!(stats.acquisitions, 2);
+//! assert_eq!(stats.contended, 0);
+//! ```
+use crate::clock;
+use core::{
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU64, Ordering},
+};
+
+/// 直方图的桶数，第`i`个桶统计`[2^(i-1), 2^i)`的时长，第0个桶统计0
+pub const BUCKETS: usize = u64::BITS as usize + 1;
+
+/// 时长的直方图，单位和时钟相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Histogram {
+    pub buckets: [u64; BUCKETS],
+}
+
+impl Histogram {
+    const EMPTY: Histogram = Histogram {
+        buckets: [0; BUCKETS],
+    };
+
+    /// 记录的次数
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// 时长`duration`所在的桶
+    pub const fn bucket(duration: u64) -> usize {
+        (u64::BITS - duration.leading_zeros()) as usize
+    }
+}
+
+/// 一把锁的统计快照
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// 获取锁的次数，包括读锁和写锁
+    pub acquisitions: u64,
+    /// 需要等待其他持有者释放的获取次数
+    pub contended: u64,
+    /// 等待时的自旋次数
+    pub spins: u64,
+    /// 等待时间，只统计阻塞的获取，没有挂上[`Histograms`]时为空
+    pub wait_time: Histogram,
+    /// 持有时间，没有挂上[`Histograms`]时为空
+    pub hold_time: Histogram,
+}
+
+struct AtomicHistogram([AtomicU64; BUCKETS]);
+
+impl AtomicHistogram {
+    const fn new() -> Self {
+        AtomicHistogram([const { AtomicU64::new(0) }; BUCKETS])
+    }
+
+    #[inline]
+    fn record(&self, duration: u64) {
+        self.0[Histogram::bucket(duration)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Histogram {
+        Histogram {
+            buckets: core::array::from_fn(|i| self.0[i].load(Ordering::Relaxed)),
+        }
+    }
+
+    fn reset(&self) {
+        for bucket in &self.0 {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 一把锁的等待时间和持有时间的直方图，放在锁外面，
+/// 用`Mutex::set_histograms`或`RWLock::set_histograms`挂到锁上
+/// # Example
+/// ```
+/// use xx_mutex_lock::{stats::Histograms, Mutex};
+///
+/// static LOCK: Mutex<u32> = Mutex::new(0);
+/// static LOCK_HISTOGRAMS: Histograms = Histograms::new();
+///
+/// LOCK.set_histograms(&LOCK_HISTOGRAMS);
+/// ```
+pub struct Histograms {
+    wait_time: AtomicHistogram,
+    hold_time: AtomicHistogram,
+}
+
+impl Histograms {
+    pub const fn new() -> Self {
+        Histograms {
+            wait_time: AtomicHistogram::new(),
+            hold_time: AtomicHistogram::new(),
+        }
+    }
+}
+
+impl Default for Histograms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 锁里保存的计数器
+pub(crate) struct LockStats {
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    spins: AtomicU64,
+    /// 挂上的直方图，为空时不计时
+    histograms: AtomicPtr<Histograms>,
+}
+
+impl LockStats {
+    pub const fn new() -> Self {
+        LockStats {
+            acquisitions: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            spins: AtomicU64::new(0),
+            histograms: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    #[inline]
+    fn histograms(&self) -> Option<&'static Histograms> {
+        //只会存入set_histograms中的静态引用
+        unsafe { self.histograms.load(Ordering::Acquire).as_ref() }
+    }
+
+    pub fn set_histograms(&self, histograms: &'static Histograms) {
+        self.histograms.store(
+            histograms as *const Histograms as *mut Histograms,
+            Ordering::Release,
+        );
+    }
+
+    /// 开始等待的时间，没有挂上直方图时不读时钟
+    #[inline]
+    pub fn start(&self) -> Option<u64> {
+        self.histograms().and_then(|_| clock::now())
+    }
+
+    /// 拿到锁之后记录，`spins`为0表示没有等待，`start`是[`LockStats::start`]的结果
+    #[inline]
+    pub fn acquired(&self, spins: u64, start: Option<u64>) {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        if spins != 0 {
+            self.contended.fetch_add(1, Ordering::Relaxed);
+            self.spins.fetch_add(spins, Ordering::Relaxed);
+        }
+        if let (Some(histograms), Some(start)) = (self.histograms(), start) {
+            if let Some(now) = clock::now() {
+                histograms.wait_time.record(now.saturating_sub(start));
+            }
+        }
+    }
+
+    /// 开始持有锁
+    #[inline]
+    pub fn hold(&self) -> Hold<'_> {
+        Hold {
+            stats: self,
+            since: self.start(),
+        }
+    }
+
+    /// 释放锁之前记录持有时间，`since`是[`Hold`]里记录的时间
+    #[inline]
+    pub fn released(&self, since: Option<u64>) {
+        if let (Some(histograms), Some(since)) = (self.histograms(), since) {
+            if let Some(now) = clock::now() {
+                histograms.hold_time.record(now.saturating_sub(since));
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Stats {
+        Stats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            spins: self.spins.load(Ordering::Relaxed),
+            wait_time: self
+                .histograms()
+                .map_or(Histogram::EMPTY, |h| h.wait_time.snapshot()),
+            hold_time: self
+                .histograms()
+                .map_or(Histogram::EMPTY, |h| h.hold_time.snapshot()),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.acquisitions.store(0, Ordering::Relaxed);
+        self.contended.store(0, Ordering::Relaxed);
+        self.spins.store(0, Ordering::Relaxed);
+        if let Some(histograms) = self.histograms() {
+            histograms.wait_time.reset();
+            histograms.hold_time.reset();
+        }
+    }
+}
+
+/// 守卫里记录的持有开始时间
+#[derive(Clone, Copy)]
+pub(crate) struct Hold<'a> {
+    pub stats: &'a LockStats,
+    pub since: Option<u64>,
+}
+
+impl Hold<'_> {
+    /// 释放锁之前调用
+    #[inline]
+    pub fn release(&self) {
+        self.stats.released(self.since)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::stats::{Histogram, Histograms};
+    use crate::{clock, test_util::unpoison, Mutex, RWLock};
+    use std::sync::{atomic::AtomicU64, atomic::Ordering, Arc};
+
+    static NOW: AtomicU64 = AtomicU64::new(0);
+
+    fn now() -> u64 {
+        //每次读取时间前进一个单位，不依赖真实的时钟
+        NOW.fetch_add(1, Ordering::Relaxed)
+    }
+
+    #[test]
+    fn test_histogram() {
+        assert_eq!(Histogram::bucket(0), 0);
+        assert_eq!(Histogram::bucket(1), 1);
+        assert_eq!(Histogram::bucket(3), 2);
+        assert_eq!(Histogram::bucket(4), 3);
+        assert_eq!(Histogram::bucket(u64::MAX), 64);
+    }
+
+    #[test]
+    fn test_stats() {
+        static LOCK_HISTOGRAMS: Histograms = Histograms::new();
+        static RW_HISTOGRAMS: Histograms = Histograms::new();
+        clock::set_clock(Some(now));
+        let lock = Arc::new(Mutex::new(0));
+        let rw = RWLock::new(0);
+        //没有挂上直方图时只有计数
+        drop(unpoison(rw.read()));
+        assert_eq!(rw.stats().acquisitions, 1);
+        assert_eq!(rw.stats().hold_time.count(), 0);
+        rw.reset_stats();
+
+        lock.set_histograms(&LOCK_HISTOGRAMS);
+        rw.set_histograms(&RW_HISTOGRAMS);
+        drop(unpoison(rw.read()));
+        drop(unpoison(rw.write()));
+        assert_eq!(rw.stats().acquisitions, 2);
+        assert_eq!(rw.stats().hold_time.count(), 2);
+
+        let guard = unpoison(lock.lock());
+        let handle = {
+            let lock = lock.clone();
+            std::thread::spawn(move || *unpoison(lock.lock()) += 1)
+        };
+        //让另一个线程开始自旋
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        drop(guard);
+        handle.join().expect("Err");
+
+        let stats = lock.stats();
+        assert_eq!(stats.acquisitions, 2);
+        assert_eq!(stats.hold_time.count(), 2);
+        assert_eq!(stats.wait_time.count(), 2);
+        assert_eq!(stats.contended, 1);
+        assert!(stats.spins > 0);
+
+        lock.reset_stats();
+        let stats = lock.stats();
+        assert_eq!(stats.acquisitions, 0);
+        assert_eq!(stats.hold_time.count(), 0);
+        clock::set_clock(None);
+    }
+}
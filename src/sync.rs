@@ -0,0 +1,140 @@
+//! 锁内部使用的原子类型、`UnsafeCell`和等待时的提示
+//!
+//! 平时就是`core`和`std`里的实现，`cfg(loom)`下换成loom的实现，用于模型检查内存序。
+//! loom的类型不能在常量里创建，所以这时`loom_const_fn!`声明的构造函数不是`const fn`
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicIsize, AtomicU8};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicIsize, AtomicU8};
+
+//loom需要在自旋时切换到其他线程，否则会一直探索同一个线程的循环
+#[cfg(not(loom))]
+pub(crate) use core::hint::spin_loop;
+#[cfg(loom)]
+pub(crate) use loom::hint::spin_loop;
+#[cfg(all(feature = "_std", loom))]
+pub(crate) use loom::thread::yield_now;
+#[cfg(all(feature = "_std", not(loom)))]
+pub(crate) use std::thread::yield_now;
+
+/// 被锁保护的数据
+///
+/// `get`表示独占的访问，`get_shared`表示共享的访问，返回的[`Access`]存在期间都算作在访问，
+/// loom据此检查这段时间里有没有其他线程的冲突访问。
+/// 只在一处读写时用`with`和`with_mut`，访问在闭包返回时结束
+#[cfg(not(loom))]
+pub(crate) struct UnsafeCell<T: ?Sized>(core::cell::UnsafeCell<T>);
+
+/// 访问登记在`track`上，数据放在`core`的`UnsafeCell`里，
+/// 这样守卫映射到数据的一部分之后还能继续持有原来的登记
+#[cfg(loom)]
+pub(crate) struct UnsafeCell<T: ?Sized> {
+    track: loom::cell::UnsafeCell<()>,
+    data: core::cell::UnsafeCell<T>,
+}
+
+/// 对[`UnsafeCell`]的一次访问，解锁之前要调用[`Access::end`]，
+/// 否则loom会把下一个持有者的访问当成数据竞争
+#[cfg(not(loom))]
+#[derive(Default)]
+pub(crate) struct Access;
+
+#[cfg(loom)]
+#[derive(Default)]
+pub(crate) struct Access {
+    _shared: Option<loom::cell::ConstPtr<()>>,
+    _exclusive: Option<loom::cell::MutPtr<()>>,
+}
+
+impl Access {
+    /// 结束访问，转移到其他守卫上的访问用`mem::take`取走
+    #[inline]
+    pub fn end(&mut self) {
+        let _ = core::mem::take(self);
+    }
+}
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    pub const fn new(data: T) -> Self {
+        UnsafeCell(core::cell::UnsafeCell::new(data))
+    }
+}
+
+#[cfg(not(loom))]
+impl<T: ?Sized> UnsafeCell<T> {
+    /// 独占访问的指针
+    #[inline]
+    pub fn get(&self) -> (*mut T, Access) {
+        (self.0.get(), Access)
+    }
+
+    /// 共享访问的指针
+    #[inline]
+    pub fn get_shared(&self) -> (*const T, Access) {
+        (self.0.get(), Access)
+    }
+
+    #[inline]
+    pub fn with_mut<U>(&self, f: impl FnOnce(*mut T) -> U) -> U {
+        f(self.0.get())
+    }
+
+    #[inline]
+    pub fn with<U>(&self, f: impl FnOnce(*const T) -> U) -> U {
+        f(self.0.get())
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+}
+
+#[cfg(loom)]
+impl<T> UnsafeCell<T> {
+    pub fn new(data: T) -> Self {
+        UnsafeCell {
+            track: loom::cell::UnsafeCell::new(()),
+            data: core::cell::UnsafeCell::new(data),
+        }
+    }
+}
+
+#[cfg(loom)]
+impl<T: ?Sized> UnsafeCell<T> {
+    /// 独占访问的指针
+    #[inline]
+    pub fn get(&self) -> (*mut T, Access) {
+        let access = Access {
+            _shared: None,
+            _exclusive: Some(self.track.get_mut()),
+        };
+        (self.data.get(), access)
+    }
+
+    /// 共享访问的指针
+    #[inline]
+    pub fn get_shared(&self) -> (*const T, Access) {
+        let access = Access {
+            _shared: Some(self.track.get()),
+            _exclusive: None,
+        };
+        (self.data.get(), access)
+    }
+
+    #[inline]
+    pub fn with_mut<U>(&self, f: impl FnOnce(*mut T) -> U) -> U {
+        self.track.with_mut(|_| f(self.data.get()))
+    }
+
+    #[inline]
+    pub fn with<U>(&self, f: impl FnOnce(*const T) -> U) -> U {
+        self.track.with(|_| f(self.data.get()))
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
@@ -0,0 +1,309 @@
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::relax::{Relax, Spin};
+
+///
+/// 公平互斥锁(排号自旋锁)
+/// 每个线程上锁时先取一个号，然后等待叫号，
+/// 按照取号的先后顺序(FIFO)获得锁，不会出现某个线程一直抢不到锁的情况
+/// # Exapmle
+///
+/// ```
+/// use xx_mutex_lock::TicketMutex;
+///
+/// let locked = TicketMutex::new(1);
+/// let mut lock_guard = locked.lock();
+/// *lock_guard += 1;
+/// assert_eq!(*lock_guard, 2)
+/// ```
+/// 当guard被drop时，自动解锁并叫下一个号
+pub struct TicketMutex<T: ?Sized, R = Spin> {
+    pub(crate) raw: RawTicket<R>,
+    data: UnsafeCell<T>,
+}
+
+/// 不保护任何数据的排号自旋锁，[`TicketMutex`]的底层实现
+/// 需要手动调用`lock`和`unlock`，
+/// 在`lock_api` feature下实现了`lock_api::RawMutex`，参见[`crate::lock_api`]
+pub struct RawTicket<R = Spin> {
+    /// 下一个要发出的号
+    pub(crate) next_ticket: AtomicUsize,
+    /// 当前持有锁的号
+    pub(crate) now_serving: AtomicUsize,
+    _relax: PhantomData<R>,
+}
+
+/// 排号互斥锁守卫
+/// 当guard被drop时，自动解锁
+pub struct TicketMutexGuard<'a, T: ?Sized + 'a> {
+    now_serving: &'a AtomicUsize,
+    data: *mut T,
+}
+
+unsafe impl<T: ?Sized + Send, R> Sync for TicketMutex<T, R> {}
+unsafe impl<T: ?Sized + Send, R> Send for TicketMutex<T, R> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for TicketMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Send> Send for TicketMutexGuard<'_, T> {}
+
+impl RawTicket {
+    pub const fn new() -> Self {
+        Self::with_relax()
+    }
+}
+
+impl Default for RawTicket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> RawTicket<R> {
+    /// 使用指定的等待策略创建排号自旋锁
+    pub const fn with_relax() -> Self {
+        RawTicket {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            _relax: PhantomData,
+        }
+    }
+
+    /// 锁是否被持有(或有线程在排队)，只用于调试，返回值可能立即过时
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+    }
+
+    /// 非阻塞地上锁，只有在没有人持有锁也没有人排队时才会成功，成功时返回true
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        //只有下一个号正好是当前叫到的号时，才取号
+        let ticket = self.now_serving.load(Ordering::Relaxed);
+        self.next_ticket
+            .compare_exchange(
+                ticket,
+                ticket.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    /// 解锁并叫下一个号
+    ///
+    /// # Safety
+    /// 只能由持有锁的一方调用
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        self.now_serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl<R: Relax> RawTicket<R> {
+    /// 上锁，按照取号的顺序获得锁，没有轮到时按照`R`的策略等待
+    #[inline]
+    pub fn lock(&self) {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let mut relax = R::default();
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            relax.relax();
+        }
+    }
+}
+
+impl<T> TicketMutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self::with_relax(data)
+    }
+}
+
+impl<T, R> TicketMutex<T, R> {
+    /// 使用指定的等待策略创建排号互斥锁
+    pub const fn with_relax(data: T) -> Self {
+        TicketMutex {
+            raw: RawTicket::with_relax(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized, R> TicketMutex<T, R> {
+    /// 锁是否被持有(或有线程在排队)，只用于调试，返回值可能立即过时
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// 非阻塞地上锁，只有在没有人持有锁也没有人排队时才会成功
+    /// # Example
+    /// ```
+    /// use xx_mutex_lock::TicketMutex;
+    ///
+    /// let locked = TicketMutex::new(1);
+    /// let lock_guard = locked.try_lock();
+    /// assert!(lock_guard.is_some());
+    /// assert!(locked.try_lock().is_none());
+    /// ```
+    #[inline]
+    pub fn try_lock(&self) -> Option<TicketMutexGuard<'_, T>> {
+        if self.raw.try_lock() {
+            Some(self.guard())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn guard(&self) -> TicketMutexGuard<'_, T> {
+        TicketMutexGuard {
+            now_serving: &self.raw.now_serving,
+            data: self.data.get(),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized, R: Relax> TicketMutex<T, R> {
+    /// 上锁，按照取号的顺序获得锁
+    ///# Examle
+    /// ```
+    /// use xx_mutex_lock::TicketMutex;
+    ///
+    /// let locked = TicketMutex::new(1);
+    /// let mut lock_guard = locked.lock();
+    /// *lock_guard += 1;
+    /// assert_eq!(*lock_guard, 2)
+    /// ```
+    pub fn lock(&self) -> TicketMutexGuard<'_, T> {
+        self.raw.lock();
+        self.guard()
+    }
+}
+
+impl<'a, T: ?Sized> Deref for TicketMutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for TicketMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for TicketMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        //叫下一个号
+        self.now_serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawMutex for RawTicket<R> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = RawTicket::with_relax();
+
+    type GuardMarker = ::lock_api::GuardSend;
+
+    #[inline]
+    fn lock(&self) {
+        RawTicket::lock(self)
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        RawTicket::try_lock(self)
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        RawTicket::unlock(self)
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        RawTicket::is_locked(self)
+    }
+}
+
+/// 排号锁解锁时总是把锁交给下一个号，本身就是公平的
+#[cfg(feature = "lock_api")]
+unsafe impl<R: Relax> ::lock_api::RawMutexFair for RawTicket<R> {
+    #[inline]
+    unsafe fn unlock_fair(&self) {
+        RawTicket::unlock(self)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::ticket_mutex::TicketMutex;
+    use core::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    #[test]
+    fn test() {
+        let lock = Arc::new(TicketMutex::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        *lock.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*lock.lock(), 400);
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn test_try_lock() {
+        let lock = TicketMutex::new(1);
+        let guard = lock.try_lock();
+        assert!(guard.is_some());
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+        assert!(lock.try_lock().is_some());
+    }
+
+    #[test]
+    fn test_fifo() {
+        //主线程先持有锁，其他线程依次排队，
+        //每个线程拿到锁的顺序必须和排队的顺序一致，
+        //也就是说第n个排队的线程最多等待前面n-1个线程
+        let lock = Arc::new(TicketMutex::new(Vec::new()));
+        let guard = lock.lock();
+        let mut threads = Vec::new();
+        for i in 0..8 {
+            let t_lock = lock.clone();
+            threads.push(std::thread::spawn(move || {
+                t_lock.lock().push(i);
+            }));
+            //等待这个线程取到号之后再启动下一个线程
+            while lock.raw.next_ticket.load(Ordering::Relaxed) != i + 2 {
+                std::thread::yield_now();
+            }
+        }
+        drop(guard);
+        for t in threads {
+            t.join().expect("err");
+        }
+        assert_eq!(*lock.lock(), (0..8).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,195 @@
+//! `tracing`集成，只在`tracing` feature下可用
+//!
+//! 开启后以下情况会通过`tracing`发出事件，target为`xx_mutex_lock::trace`：
+//! - [`Mutex`](crate::Mutex)和[`RWLock`](crate::RWLock)需要等待才拿到锁时，
+//!   发出DEBUG级别的`contended acquisition`事件，带上等待的自旋次数
+//! - 一次等待的自旋次数达到[`LONG_WAIT_SPINS`]时，发出WARN级别的`long wait`事件
+//! - [`OnceLock`](crate::OnceLock)和[`LazyLock`](crate::LazyLock)初始化时进入`init` span，
+//!   并在其中发出`init start`和`init finish`事件
+//! - `std` feature下锁中毒时，发出WARN级别的`lock poisoned`事件
+//!
+//! 事件带有`kind`（锁的类型）、`addr`（锁的地址）和`name`字段，
+//! `name`由`Mutex::named`之类的构造函数设置，没有名字时不记录
+//! # Example
+//! ```
+//! use xx_mutex_lock::Mutex;
+//!
+//! //等待DEVICES的事件和span会带上name = "devices"
+//! static DEVICES: Mutex<u32> = Mutex::named("devices", 0);
+//! ```
+
+/// 一次等待的自旋次数达到这个值时发出`long wait`事件，每次等待只发出一次
+pub const LONG_WAIT_SPINS: u64 = 1 << 16;
+
+/// 等待之后拿到了锁
+#[cold]
+pub(crate) fn contended(
+    kind: &'static str,
+    name: Option<&'static str>,
+    addr: *const (),
+    spins: u64,
+) {
+    tracing::debug!(kind, name = name, addr = ?addr, spins, "contended acquisition");
+}
+
+/// 等待时调用，自旋次数达到[`LONG_WAIT_SPINS`]时发出事件
+#[inline]
+pub(crate) fn waiting(kind: &'static str, name: Option<&'static str>, addr: *const (), spins: u64) {
+    if spins == LONG_WAIT_SPINS {
+        long_wait(kind, name, addr, spins);
+    }
+}
+
+#[cold]
+fn long_wait(kind: &'static str, name: Option<&'static str>, addr: *const (), spins: u64) {
+    tracing::warn!(kind, name = name, addr = ?addr, spins, "long wait");
+}
+
+/// 锁的标识，守卫里保存一份，解锁时用于中毒事件
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LockId {
+    pub kind: &'static str,
+    pub name: Option<&'static str>,
+    pub addr: *const (),
+}
+
+/// 锁被panic的线程标记为中毒
+#[cfg(feature = "std")]
+#[cold]
+pub(crate) fn poisoned(kind: &'static str, name: Option<&'static str>, addr: *const ()) {
+    tracing::warn!(kind, name = name, addr = ?addr, "lock poisoned");
+}
+
+/// 在`init` span里运行初始化函数`f`
+#[cold]
+pub(crate) fn init<U>(
+    kind: &'static str,
+    name: Option<&'static str>,
+    addr: *const (),
+    ty: &'static str,
+    f: impl FnOnce() -> U,
+) -> U {
+    let span = tracing::debug_span!("init", kind, name = name, addr = ?addr, ty);
+    span.in_scope(|| {
+        tracing::debug!("init start");
+        let res = f();
+        tracing::debug!("init finish");
+        res
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+pub mod test {
+    extern crate std;
+    use crate::{test_util::unpoison, LazyLock, Mutex, OnceLock};
+    use std::{
+        string::{String, ToString},
+        sync::{Arc, Mutex as StdMutex},
+        vec::Vec,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    /// 记录所有事件的消息和name字段
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<StdMutex<Vec<String>>>);
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "name" {
+                self.0 += " ";
+                self.0 += value;
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+            if field.name() == "message" {
+                self.0.insert_str(0, &std::format!("{:?}", value));
+            }
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut fields = Fields(String::new());
+            span.record(&mut fields);
+            self.0
+                .lock()
+                .unwrap()
+                .push(span.metadata().name().to_string() + &fields.0);
+            span::Id::from_u64(1)
+        }
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(String::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+        fn enter(&self, _: &span::Id) {}
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn test_events() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let cell: OnceLock<i32> = OnceLock::named("cell");
+            cell.get_or_init(|| 1);
+            let lazy: LazyLock<i32> = LazyLock::named("lazy", || 2);
+            assert_eq!(*lazy, 2);
+
+            //持有锁的时候从另一个线程等待，用同一个subscriber记录
+            let lock = Arc::new(Mutex::named("lock", 0));
+            let guard = unpoison(lock.lock());
+            let handle = {
+                let lock = lock.clone();
+                let recorder = recorder.clone();
+                std::thread::spawn(move || {
+                    tracing::subscriber::with_default(recorder, || *unpoison(lock.lock()) += 1)
+                })
+            };
+            //等到另一个线程自旋了足够长的时间
+            while !recorder
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|e| e == "long wait lock")
+            {
+                std::thread::yield_now();
+            }
+            drop(guard);
+            handle.join().expect("Err");
+
+            //中毒事件带上锁的名字
+            let poisoned = Arc::new(Mutex::named("poisoned", 0));
+            let res = {
+                let poisoned = poisoned.clone();
+                let recorder = recorder.clone();
+                std::thread::spawn(move || {
+                    tracing::subscriber::with_default(recorder, || {
+                        let _guard = unpoison(poisoned.lock());
+                        panic!("poison the lock");
+                    })
+                })
+                .join()
+            };
+            assert!(res.is_err());
+        });
+        let events = recorder.0.lock().unwrap();
+        assert!(events.contains(&"init cell".to_string()));
+        assert!(events.contains(&"init lazy".to_string()));
+        assert_eq!(events.iter().filter(|e| *e == "init finish").count(), 2);
+        assert!(events.contains(&"contended acquisition lock".to_string()));
+        assert!(events.contains(&"lock poisoned poisoned".to_string()));
+    }
+}
@@ -0,0 +1,174 @@
+//! 等待中的任务的Waker集合，给异步的锁使用
+//!
+//! 侵入式的双向链表，节点[`Waiter`]保存在等待的future里，不需要堆分配，
+//! 也没有数量限制，等待的任务再多也不会互相挤掉。
+//! 节点登记之后不能移动，所以保存节点的future不实现`Unpin`，并且在drop时取消登记
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomPinned,
+    pin::Pin,
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+    task::Waker,
+};
+
+use crate::mutex::RawSpin;
+
+/// 链表的节点
+pub(crate) struct Waiter {
+    /// 只在持有集合内部的锁时访问
+    node: UnsafeCell<Node>,
+    /// 是否登记过，只由持有节点的future读写，没有登记过时取消登记不需要上锁
+    registered: AtomicBool,
+    _pin: PhantomPinned,
+}
+
+struct Node {
+    /// 为None表示没有登记在集合里
+    waker: Option<Waker>,
+    prev: *const Waiter,
+    next: *const Waiter,
+}
+
+//`node`只在持有集合内部的锁时访问
+unsafe impl Send for Waiter {}
+unsafe impl Sync for Waiter {}
+
+impl Waiter {
+    pub const fn new() -> Self {
+        Waiter {
+            node: UnsafeCell::new(Node {
+                waker: None,
+                prev: ptr::null(),
+                next: ptr::null(),
+            }),
+            registered: AtomicBool::new(false),
+            _pin: PhantomPinned,
+        }
+    }
+}
+
+pub(crate) struct WakerSet {
+    lock: RawSpin,
+    inner: UnsafeCell<Inner>,
+}
+
+struct Inner {
+    head: *const Waiter,
+    tail: *const Waiter,
+    len: usize,
+}
+
+unsafe impl Send for WakerSet {}
+unsafe impl Sync for WakerSet {}
+
+impl Inner {
+    /// 调用者持有集合内部的锁，并保证`waiter`没有登记
+    unsafe fn push_back(&mut self, waiter: *const Waiter, waker: Waker) {
+        let node = &mut *(*waiter).node.get();
+        node.waker = Some(waker);
+        node.prev = self.tail;
+        node.next = ptr::null();
+        if self.tail.is_null() {
+            self.head = waiter;
+        } else {
+            (*(*self.tail).node.get()).next = waiter;
+        }
+        self.tail = waiter;
+        self.len += 1;
+    }
+
+    /// 调用者持有集合内部的锁，并保证`waiter`登记在这个集合里
+    unsafe fn unlink(&mut self, waiter: *const Waiter) -> Option<Waker> {
+        let node = &mut *(*waiter).node.get();
+        if node.prev.is_null() {
+            self.head = node.next;
+        } else {
+            (*(*node.prev).node.get()).next = node.next;
+        }
+        if node.next.is_null() {
+            self.tail = node.prev;
+        } else {
+            (*(*node.next).node.get()).prev = node.prev;
+        }
+        node.prev = ptr::null();
+        node.next = ptr::null();
+        self.len -= 1;
+        node.waker.take()
+    }
+}
+
+impl WakerSet {
+    loom_const_fn! {
+        pub const fn new() -> Self {
+            WakerSet {
+                lock: RawSpin::new(),
+                inner: UnsafeCell::new(Inner {
+                    head: ptr::null(),
+                    tail: ptr::null(),
+                    len: 0,
+                }),
+            }
+        }
+    }
+
+    #[inline]
+    fn with<U>(&self, f: impl FnOnce(&mut Inner) -> U) -> U {
+        self.lock.lock();
+        let res = f(unsafe { &mut *self.inner.get() });
+        unsafe { self.lock.unlock() };
+        res
+    }
+
+    /// 登记当前任务的Waker，已经登记过的话只更新Waker
+    ///
+    /// 同一个节点只能登记在一个集合里，并且在被drop之前要调用[`WakerSet::remove`]
+    pub fn register(&self, waiter: Pin<&Waiter>, waker: &Waker) {
+        let waiter = waiter.get_ref();
+        waiter.registered.store(true, Ordering::Relaxed);
+        self.with(|inner| unsafe {
+            match &mut (*waiter.node.get()).waker {
+                Some(w) => {
+                    if !w.will_wake(waker) {
+                        w.clone_from(waker);
+                    }
+                }
+                None => inner.push_back(waiter, waker.clone()),
+            }
+        });
+    }
+
+    /// 取消登记，用于future被drop或者已经完成的时候，没有登记时什么都不做
+    pub fn remove(&self, waiter: &Waiter) {
+        if !waiter.registered.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        self.with(|inner| unsafe {
+            if (*waiter.node.get()).waker.is_some() {
+                inner.unlink(waiter);
+            }
+        });
+    }
+
+    /// 唤醒所有登记过的任务
+    pub fn wake_all(&self) {
+        //只唤醒调用时已经登记的任务，被唤醒的任务立即重新登记时排在后面，不会一直循环
+        let mut count = self.with(|inner| inner.len);
+        while count > 0 {
+            count -= 1;
+            let waker = self.with(|inner| {
+                let head = inner.head;
+                if head.is_null() {
+                    None
+                } else {
+                    unsafe { inner.unlink(head) }
+                }
+            });
+            //在释放内部的锁之后再唤醒，避免被唤醒的任务立即登记时自旋
+            match waker {
+                Some(waker) => waker.wake(),
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! 长时间自旋的看门狗，只在`watchdog` feature下可用
+//!
+//! [`Mutex::lock`](crate::Mutex::lock)、[`RWLock`](crate::RWLock)的`read`/`write`
+//! 和`Once`等待初始化时，每次自旋都会计数。
+//! 超过[`set_watchdog`]设置的次数，或者设置了[`crate::clock`]后超过[`set_time_budget`]时，
+//! 调用注册的回调报告锁的地址、名字、状态和持有者，之后重新计数，
+//! 所以一直卡住时回调会被周期性地调用。
+//! 回调返回后继续自旋，需要中止时可以在回调里panic
+//! # Example
+//! ```
+//! use xx_mutex_lock::watchdog::{self, Report};
+//!
+//! fn soft_lockup(report: &Report) {
+//!     println!("{}", report);
+//! }
+//!
+//! //每自旋一百万次报告一次
+//! watchdog::set_watchdog(1_000_000, Some(soft_lockup));
+//! ```
+use crate::clock;
+use core::{
+    fmt,
+    num::NonZeroUsize,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+/// 设置时钟，原来的路径，参见[`crate::clock::set_clock`]
+pub use crate::clock::set_clock;
+
+/// 看门狗的报告
+#[derive(Debug, Clone, Copy)]
+pub struct Report {
+    /// 锁的地址
+    pub lock: usize,
+    /// 锁的种类，例如`"Mutex"`
+    pub kind: &'static str,
+    /// 锁的名字，参见[`Mutex::named`](crate::Mutex::named)
+    pub name: Option<&'static str>,
+    /// 锁的原始状态：`Mutex`为0或1，`RWLock`为读者数量或-1，`Once`为[`status`](crate::once::status)
+    pub state: isize,
+    /// 持有者的标识，只在能记录持有者时有值，参见[`crate::owner`]
+    pub owner: Option<NonZeroUsize>,
+    /// 这次等待已经自旋的次数
+    pub spins: u64,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "soft lockup: {} {:#x}", self.kind, self.lock)?;
+        if let Some(name) = self.name {
+            write!(f, " ({})", name)?;
+        }
+        write!(f, " state {} after {} spins", self.state, self.spins)?;
+        if let Some(owner) = self.owner {
+            write!(f, ", held by {:#x}", owner)?;
+        }
+        Ok(())
+    }
+}
+
+//预算用usize保存，没有64位原子类型的目标(例如thumbv6、riscv32imac)上也能使用
+/// 自旋次数预算，0表示不按次数检查
+static SPIN_BUDGET: AtomicUsize = AtomicUsize::new(0);
+/// 时间预算，单位和时钟相同，0表示不按时间检查
+static TIME_BUDGET: AtomicUsize = AtomicUsize::new(0);
+static HANDLER: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// 检查时钟的间隔，避免每次自旋都读时钟
+const CLOCK_INTERVAL: u64 = 64;
+
+/// 预算超过`usize::MAX`时按`usize::MAX`保存
+#[inline]
+fn saturate(budget: u64) -> usize {
+    usize::try_from(budget).unwrap_or(usize::MAX)
+}
+
+/// 设置自旋次数预算和回调，回调为None时关闭看门狗
+/// 32位目标上超过`usize::MAX`的预算按`usize::MAX`处理
+pub fn set_watchdog(spins: u64, handler: Option<fn(&Report)>) {
+    SPIN_BUDGET.store(saturate(spins), Ordering::Relaxed);
+    HANDLER.store(
+        handler.map_or(ptr::null_mut(), |h| h as *mut ()),
+        Ordering::Release,
+    );
+}
+
+/// 设置时间预算，单位和[`crate::clock`]设置的时钟相同，0表示只按自旋次数检查，
+/// 32位目标上超过`usize::MAX`的预算按`usize::MAX`处理
+/// # Example
+/// ```
+/// use std::time::Instant;
+/// use xx_mutex_lock::{clock, watchdog};
+///
+/// fn now() -> u64 {
+///     static START: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+///     START.get_or_init(Instant::now).elapsed().as_millis() as u64
+/// }
+///
+/// //自旋超过一秒时报告
+/// clock::set_clock(Some(now));
+/// watchdog::set_time_budget(1000);
+/// ```
+pub fn set_time_budget(budget: u64) {
+    TIME_BUDGET.store(saturate(budget), Ordering::Relaxed);
+}
+
+#[inline]
+fn handler() -> Option<fn(&Report)> {
+    let handler = HANDLER.load(Ordering::Acquire);
+    //只会存入set_watchdog中的函数指针
+    (!handler.is_null()).then(|| unsafe { core::mem::transmute::<*mut (), fn(&Report)>(handler) })
+}
+
+/// 一次等待的计数
+pub(crate) struct Watchdog {
+    spins: u64,
+    /// 上一次计时开始的时间
+    start: Option<u64>,
+    /// 上一次报告时的自旋次数
+    reported: u64,
+}
+
+impl Watchdog {
+    #[inline]
+    pub fn new() -> Self {
+        Watchdog {
+            spins: 0,
+            start: None,
+            reported: 0,
+        }
+    }
+
+    /// 每次自旋调用一次，超过预算时用`report`生成报告交给回调
+    #[inline]
+    pub fn tick(&mut self, report: impl FnOnce(u64) -> Report) {
+        self.spins += 1;
+        let Some(handler) = handler() else {
+            return;
+        };
+
+        let budget = SPIN_BUDGET.load(Ordering::Relaxed) as u64;
+        let mut expired = budget != 0 && self.spins - self.reported >= budget;
+        if self.spins.is_multiple_of(CLOCK_INTERVAL) {
+            if let Some(now) = clock::now() {
+                let start = *self.start.get_or_insert(now);
+                let budget = TIME_BUDGET.load(Ordering::Relaxed) as u64;
+                expired |= budget != 0 && now.wrapping_sub(start) >= budget;
+            }
+        }
+        if expired {
+            self.reported = self.spins;
+            self.start = None;
+            handler(&report(self.spins));
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    extern crate std;
+    use crate::test_util::unpoison;
+    use crate::watchdog::{self, Report};
+    use crate::{Mutex, OnceLock, RWLock};
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex as StdMutex};
+    use std::time::{Duration, Instant};
+    use std::vec::Vec;
+
+    static REPORTS: StdMutex<Vec<Report>> = StdMutex::new(Vec::new());
+
+    fn record(report: &Report) {
+        REPORTS.lock().unwrap().push(*report);
+    }
+
+    /// 等待名字为name的报告
+    fn wait_report(name: &str) -> Report {
+        let start = Instant::now();
+        loop {
+            assert!(start.elapsed() < Duration::from_secs(10), "no report");
+            if let Some(report) = REPORTS
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|r| r.name == Some(name))
+            {
+                return *report;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_watchdog() {
+        watchdog::set_watchdog(1000, Some(record));
+
+        let mutex = Arc::new(Mutex::named("watchdog mutex", 0));
+        let guard = unpoison(mutex.lock());
+        let t = {
+            let mutex = mutex.clone();
+            std::thread::spawn(move || drop(unpoison(mutex.lock())))
+        };
+        let report = wait_report("watchdog mutex");
+        assert_eq!(report.kind, "Mutex");
+        assert_eq!(report.lock, &*mutex as *const _ as usize);
+        assert_eq!(report.state, 1);
+        assert!(report.spins >= 1000);
+        //报告之后继续等待，释放锁后可以拿到
+        drop(guard);
+        t.join().expect("err");
+
+        let rw = Arc::new(RWLock::named("watchdog rwlock", 0));
+        let guard = unpoison(rw.write());
+        let t = {
+            let rw = rw.clone();
+            std::thread::spawn(move || drop(unpoison(rw.read())))
+        };
+        let report = wait_report("watchdog rwlock");
+        assert_eq!(report.kind, "RWLock");
+        assert_eq!(report.state, -1);
+        drop(guard);
+        t.join().expect("err");
+
+        //等待另一个线程初始化OnceLock，报告里带上OnceLock的名字
+        let cell: Arc<OnceLock<i32>> = Arc::new(OnceLock::named("watchdog once"));
+        let started = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicBool::new(false));
+        let init = {
+            let (cell, started, done) = (cell.clone(), started.clone(), done.clone());
+            std::thread::spawn(move || {
+                cell.get_or_init(|| {
+                    started.store(true, Ordering::Release);
+                    while !done.load(Ordering::Acquire) {
+                        std::thread::yield_now();
+                    }
+                    1
+                });
+            })
+        };
+        while !started.load(Ordering::Acquire) {
+            std::thread::yield_now();
+        }
+        let t = {
+            let cell = cell.clone();
+            std::thread::spawn(move || assert_eq!(*cell.get_or_init(|| 2), 1))
+        };
+        let report = wait_report("watchdog once");
+        assert_eq!(report.kind, "Once");
+        done.store(true, Ordering::Release);
+        init.join().expect("err");
+        t.join().expect("err");
+    }
+}